@@ -22,7 +22,7 @@ pub enum Commands {
     /// Static file server
     StaticServer(StaticServerArgs),
     /// Start a json server
-    JsonServer(JsonServerArgs),
+    JsonServer(Box<JsonServerArgs>),
     /// Preview image
     ImagePreview(ImagePreviewArgs),
     /// Base64 encode or decode
@@ -37,8 +37,8 @@ impl AesArgs {
         // 通过 sha256 生成 32 字节的密钥
         let mut hasher = Sha256::new();
         hasher.update(self.key.as_bytes());
-        let key = hasher.finalize().into();
-        key
+
+        hasher.finalize().into()
     }
 
     /// 生成随机 iv
@@ -95,7 +95,7 @@ impl AesArgs {
             .unwrap();
 
         // &[u8] -> String
-        String::from_utf8_lossy(&ct).to_string()
+        String::from_utf8_lossy(ct).to_string()
     }
 }
 
@@ -46,8 +46,8 @@ impl RunCommand for FindArgs {
                     // print header
                     if count == 1 {
                         println!(
-                            "{:<5} {:<20} {:<30} {}",
-                            "No", "File Name", "Relative Path", "Full Path"
+                            "{:<5} {:<20} {:<30} Full Path",
+                            "No", "File Name", "Relative Path"
                         );
                     }
                     // print result
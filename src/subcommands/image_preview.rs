@@ -63,7 +63,7 @@ impl RunCommand for ImagePreviewArgs {
 }
 
 /// 获取文件类型, 默认所有类型
-fn get_file_type<'s>(image_type: Option<&'s str>) -> Result<Vec<&'s str>, &'static str> {
+fn get_file_type(image_type: Option<&str>) -> Result<Vec<&str>, &'static str> {
     let file_type = vec!["svg", "png", "jpg", "jpeg", "gif"];
     match image_type {
         Some(image_type) => {
@@ -81,7 +81,6 @@ fn get_file_type<'s>(image_type: Option<&'s str>) -> Result<Vec<&'s str>, &'stat
 struct ImageFile {
     name: String,
     relative_path: String,
-    full_path: String,
 }
 
 /// 获取目录下指定类型的文件
@@ -96,18 +95,9 @@ fn get_files_from_path(path: &Path, file_type: Vec<&str>) -> Vec<ImageFile> {
                 let file_path = entry.path();
                 let relative_path = file_path.strip_prefix(path).unwrap();
                 let relative_path_str = relative_path.to_str().unwrap();
-                let full_path = file_path.canonicalize().unwrap();
-                let full_path_str = full_path.to_str().unwrap();
-                // remove prefix \\?\ for windows
-                let full_path_without_prefix = if full_path_str.starts_with("\\\\?\\") {
-                    full_path_str.strip_prefix("\\\\?\\").unwrap()
-                } else {
-                    full_path_str
-                };
                 files.push(ImageFile {
                     name: file_name.to_string(),
                     relative_path: relative_path_str.to_string(),
-                    full_path: full_path_without_prefix.to_string(),
                 });
             }
         }
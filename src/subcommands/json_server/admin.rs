@@ -0,0 +1,261 @@
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::{serde_json::json, Value};
+use rocket::{routes, Route};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::error::error;
+use super::events::Events;
+use super::mount::Base;
+use super::storage::{self, Storage};
+use super::{check_storable, not_found, Db};
+
+/// 管理接口的前缀
+pub const ADMIN_BASE: &str = "/__admin";
+
+/// 一份数据快照
+struct Snapshot {
+    /// 创建时间, unix 时间戳, 单位秒
+    created: u64,
+    data: HashMap<String, Value>,
+}
+
+/// 数据快照, 用于测试前后保存和恢复数据
+/// 指定目录时快照同时写入 `<目录>/<name>-<时间戳>.json`, 启动时读取目录中每个名称最新的快照
+pub struct Snapshots {
+    /// 启动时加载的数据, 用于重置
+    original: HashMap<String, Value>,
+    saved: Mutex<HashMap<String, Snapshot>>,
+    dir: Option<PathBuf>,
+}
+
+impl Snapshots {
+    pub fn new(
+        original: HashMap<String, Value>,
+        dir: Option<PathBuf>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut saved = HashMap::new();
+        if let Some(dir) = &dir {
+            fs::create_dir_all(dir)?;
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                let (name, created) = match parse_file_name(&path) {
+                    Some(parsed) => parsed,
+                    None => continue,
+                };
+                if saved
+                    .get(&name)
+                    .is_some_and(|s: &Snapshot| s.created >= created)
+                {
+                    continue;
+                }
+                let data = storage::load(&path)?;
+                saved.insert(name, Snapshot { created, data });
+            }
+        }
+        Ok(Snapshots {
+            original,
+            saved: Mutex::new(saved),
+            dir,
+        })
+    }
+
+    fn file_path(&self, name: &str, created: u64) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}-{}.json", name, created)))
+    }
+}
+
+/// 从文件名中解析快照名称和时间戳, 如 clean-1700000000.json
+fn parse_file_name(path: &Path) -> Option<(String, u64)> {
+    if path.extension()? != "json" {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    let (name, created) = stem.rsplit_once('-')?;
+    Some((name.to_string(), created.parse().ok()?))
+}
+
+/// 快照名称只能包含字母, 数字, - 和 _, 以便作为文件名
+fn check_name(name: &str) -> Result<(), status::Custom<Value>> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        return Ok(());
+    }
+    Err(error(
+        Status::BadRequest,
+        format!(
+            "Invalid snapshot name {}, use letters, digits, - and _",
+            name
+        ),
+    ))
+}
+
+fn snapshot_not_found(name: &str) -> status::Custom<Value> {
+    not_found(format!("Snapshot {} not found", name))
+}
+
+/// 替换全部数据并写入到文件中
+fn replace_and_write(
+    db: &Db,
+    storage: &Storage,
+    events: &Events,
+    data: &HashMap<String, Value>,
+) -> Value {
+    let mut db = db.write();
+    events.replaced(&db, data);
+    *db = data.clone();
+    storage.write(&db);
+    json!(*db)
+}
+
+/// 所有快照的名称和创建时间
+#[rocket::get("/snapshots")]
+fn list_snapshots(snapshots: &Snapshots) -> Value {
+    let saved = snapshots.saved.lock().unwrap();
+    let mut list: Vec<Value> = saved
+        .iter()
+        .map(|(name, snapshot)| json!({ "name": name, "created": snapshot.created }))
+        .collect();
+    list.sort_by_key(|item| item["created"].as_u64());
+    Value::Array(list)
+}
+
+/// 保存当前数据为快照, 同名的快照会被替换, 返回 201
+#[rocket::post("/snapshots/<name>")]
+fn create_snapshot(
+    name: &str,
+    db: &Db,
+    snapshots: &Snapshots,
+    base: Base,
+) -> Result<status::Created<Value>, status::Custom<Value>> {
+    check_name(name)?;
+    let data = db.read().clone();
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    if let Some(path) = snapshots.file_path(name, created) {
+        storage::write_atomic(&path, &data).map_err(|e| {
+            error(
+                Status::InternalServerError,
+                format!("Unable to write snapshot {}: {}", path.display(), e),
+            )
+        })?;
+    }
+    let mut saved = snapshots.saved.lock().unwrap();
+    saved.insert(name.to_string(), Snapshot { created, data });
+    let location = format!("{}{}/snapshots/{}", base, ADMIN_BASE, name);
+    Ok(status::Created::new(location).body(json!({ "name": name, "created": created })))
+}
+
+/// 快照中的数据
+#[rocket::get("/snapshots/<name>")]
+fn get_snapshot(name: &str, snapshots: &Snapshots) -> Result<Value, status::Custom<Value>> {
+    let saved = snapshots.saved.lock().unwrap();
+    match saved.get(name) {
+        Some(snapshot) => Ok(json!(snapshot.data)),
+        None => Err(snapshot_not_found(name)),
+    }
+}
+
+/// 恢复快照中的数据, 返回恢复后的数据
+#[rocket::post("/snapshots/<name>/restore")]
+fn restore_snapshot(
+    name: &str,
+    db: &Db,
+    storage: &Storage,
+    events: &Events,
+    snapshots: &Snapshots,
+) -> Result<Value, status::Custom<Value>> {
+    let saved = snapshots.saved.lock().unwrap();
+    match saved.get(name) {
+        Some(snapshot) => {
+            for value in snapshot.data.values() {
+                check_storable(storage, value)?;
+            }
+            Ok(replace_and_write(db, storage, events, &snapshot.data))
+        }
+        None => Err(snapshot_not_found(name)),
+    }
+}
+
+/// 删除快照, 包括目录中同名的快照文件, 返回 204
+#[rocket::delete("/snapshots/<name>")]
+fn delete_snapshot(name: &str, snapshots: &Snapshots) -> Result<Status, status::Custom<Value>> {
+    let mut saved = snapshots.saved.lock().unwrap();
+    if saved.remove(name).is_none() {
+        return Err(snapshot_not_found(name));
+    }
+    if let Some(dir) = &snapshots.dir {
+        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if parse_file_name(&path).is_some_and(|(file_name, _)| file_name == name) {
+                if let Err(e) = fs::remove_file(&path) {
+                    println!("Unable to remove file {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+    Ok(Status::NoContent)
+}
+
+/// 重置为启动时加载的数据, 返回重置后的数据
+#[rocket::post("/reset")]
+fn reset(db: &Db, storage: &Storage, events: &Events, snapshots: &Snapshots) -> Value {
+    replace_and_write(db, storage, events, &snapshots.original)
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        list_snapshots,
+        create_snapshot,
+        get_snapshot,
+        restore_snapshot,
+        delete_snapshot,
+        reset
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_files() {
+        assert_eq!(
+            parse_file_name(Path::new("/tmp/before-test-1700000000.json")),
+            Some((String::from("before-test"), 1700000000))
+        );
+        assert_eq!(parse_file_name(Path::new("/tmp/clean.json")), None);
+        assert_eq!(parse_file_name(Path::new("/tmp/clean-1.txt")), None);
+
+        let dir =
+            std::env::temp_dir().join(format!("json_server_snapshots_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("clean-1.json"), r#"{"posts": []}"#).unwrap();
+        fs::write(dir.join("clean-2.json"), r#"{"posts": [{"id": 1}]}"#).unwrap();
+        let snapshots = Snapshots::new(HashMap::new(), Some(dir.clone())).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+
+        let saved = snapshots.saved.lock().unwrap();
+        assert_eq!(saved["clean"].created, 2);
+        assert_eq!(saved["clean"].data["posts"], json!([{ "id": 1 }]));
+    }
+
+    #[test]
+    fn snapshot_names() {
+        assert!(check_name("before-test_1").is_ok());
+        assert!(check_name("../db").is_err());
+        assert!(check_name("").is_err());
+    }
+}
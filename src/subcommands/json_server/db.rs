@@ -0,0 +1,118 @@
+use rocket::serde::json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// 内存中的数据, 因为格式不统一, 所以只能用 Value 类型
+/// 使用 Arc 是为了在后台任务中也能访问, 如 --watch 重新加载文件
+/// 读请求可以同时进行, 写请求独占
+/// handler panic 导致锁中毒后继续使用其中的数据, 每次修改都是替换整个资源, 不会留下修改到一半的数据
+#[derive(Clone, Default)]
+pub struct Db(Arc<RwLock<HashMap<String, Value>>>);
+
+impl Db {
+    pub fn new(data: HashMap<String, Value>) -> Self {
+        Db(Arc::new(RwLock::new(data)))
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, HashMap<String, Value>> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, Value>> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::serde::json::serde_json::json;
+    use std::sync::Barrier;
+    use std::thread;
+    use std::time::Instant;
+
+    const READERS: usize = 8;
+
+    fn posts(count: usize) -> Db {
+        let items = (1..=count)
+            .map(|id| json!({ "id": id, "title": format!("post {}", id) }))
+            .collect();
+        Db::new(HashMap::from([(
+            String::from("posts"),
+            Value::Array(items),
+        )]))
+    }
+
+    #[test]
+    fn concurrent_reads() {
+        // 所有线程同时持有读锁才能通过 barrier, 读请求互相阻塞时会死锁
+        let db = posts(10);
+        let barrier = Arc::new(Barrier::new(READERS));
+        let handles: Vec<_> = (0..READERS)
+            .map(|_| {
+                let (db, barrier) = (db.clone(), barrier.clone());
+                thread::spawn(move || {
+                    let data = db.read();
+                    barrier.wait();
+                    data["posts"].as_array().unwrap().len()
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 10);
+        }
+    }
+
+    #[test]
+    fn recover_from_poison() {
+        let db = posts(1);
+        let poisoned = db.clone();
+        let _ = thread::spawn(move || {
+            let _data = poisoned.write();
+            panic!("handler panicked");
+        })
+        .join();
+        assert!(db.0.is_poisoned());
+        db.write().insert(String::from("profile"), json!({}));
+        assert_eq!(db.read().len(), 2);
+    }
+
+    /// 并发读的压测, `cargo test read_load -- --ignored --nocapture` 查看结果
+    #[test]
+    #[ignore]
+    fn read_load() {
+        let db = posts(1000);
+        let reads = 2000;
+        for threads in [1, READERS] {
+            let start = Instant::now();
+            let handles: Vec<_> = (0..threads)
+                .map(|_| {
+                    let db = db.clone();
+                    thread::spawn(move || {
+                        for _ in 0..reads {
+                            let data = db.read();
+                            let matched = data["posts"]
+                                .as_array()
+                                .unwrap()
+                                .iter()
+                                .filter(|item| item["title"].as_str().unwrap().ends_with('7'))
+                                .count();
+                            assert_eq!(matched, 100);
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+            let elapsed = start.elapsed();
+            println!(
+                "{} threads: {} reads in {:?}, {:.0} reads/s",
+                threads,
+                threads * reads,
+                elapsed,
+                (threads * reads) as f64 / elapsed.as_secs_f64()
+            );
+        }
+    }
+}
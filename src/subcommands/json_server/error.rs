@@ -0,0 +1,132 @@
+use rocket::data::{self, Data, FromData};
+use rocket::http::Status;
+use rocket::outcome::Outcome;
+use rocket::response::status;
+use rocket::serde::json::{self, serde_json::json, Json, Value};
+use rocket::serde::Deserialize;
+use rocket::Request;
+use std::ops::Deref;
+
+/// 统一的错误响应格式
+/// {"status": 404, "error": "Not Found", "message": "..."}
+pub fn error(status: Status, message: impl Into<String>) -> status::Custom<Value> {
+    status::Custom(
+        status,
+        json!({
+            "status": status.code,
+            "error": status.reason_lossy(),
+            "message": message.into()
+        }),
+    )
+}
+
+/// 带有详细信息的错误响应
+/// {"status": 409, "error": "Conflict", "message": "...", "details": {...}}
+pub fn error_with(
+    status: Status,
+    message: impl Into<String>,
+    details: Value,
+) -> status::Custom<Value> {
+    let mut err = error(status, message);
+    err.1["details"] = details;
+    err
+}
+
+/// 请求体解析失败的原因, 保存在请求的 local cache 中
+/// default_catcher 据此区分请求体错误和其他原因的 400, 422
+struct BodyError(Option<String>);
+
+/// 与 Json 相同的请求体, 解析失败时记录失败的原因
+pub struct JsonBody<T>(pub T);
+
+impl<T> JsonBody<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for JsonBody<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: Deserialize<'r>> FromData<'r> for JsonBody<T> {
+    type Error = json::Error<'r>;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        match Json::<T>::from_data(req, data).await {
+            Outcome::Success(body) => Outcome::Success(JsonBody(body.into_inner())),
+            Outcome::Error((status, e)) => {
+                req.local_cache(|| BodyError(Some(e.to_string())));
+                Outcome::Error((status, e))
+            }
+            Outcome::Forward(forward) => Outcome::Forward(forward),
+        }
+    }
+}
+
+/// Rocket 产生的错误, 如请求体不是合法的 JSON, 路由不存在, handler panic 等
+/// 也返回相同的格式, 只有请求体解析失败时才说明是请求体的问题
+#[rocket::catch(default)]
+pub fn default_catcher(status: Status, req: &Request) -> status::Custom<Value> {
+    let body_error = req.local_cache(|| BodyError(None)).0.is_some();
+    let message = match status.code {
+        400 if body_error => String::from("Request body is not valid JSON"),
+        422 if body_error => String::from("Request body does not match the expected shape"),
+        404 => format!("No route for {} {}", req.method(), req.uri()),
+        _ => status.reason_lossy().to_string(),
+    };
+    error(status, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::{catchers, routes};
+
+    #[rocket::get("/bad")]
+    fn bad() -> Status {
+        Status::BadRequest
+    }
+
+    #[rocket::post("/body", data = "<body>")]
+    fn body(body: JsonBody<Value>) -> Value {
+        body.into_inner()
+    }
+
+    #[test]
+    fn catcher_messages() {
+        let rocket = rocket::build()
+            .mount("/", routes![bad, body])
+            .register("/", catchers![default_catcher]);
+        let client = Client::tracked(rocket).unwrap();
+        let message = |response: rocket::local::blocking::LocalResponse| {
+            response.into_json::<Value>().unwrap()["message"].clone()
+        };
+        assert_eq!(
+            message(client.post("/body").body("{").dispatch()),
+            json!("Request body is not valid JSON")
+        );
+        assert_eq!(message(client.get("/bad").dispatch()), json!("Bad Request"));
+    }
+
+    #[test]
+    fn error_schema() {
+        let err = error_with(Status::Conflict, "duplicate id", json!({ "id": 1 }));
+        assert_eq!(err.0, Status::Conflict);
+        assert_eq!(
+            err.1,
+            json!({
+                "status": 409,
+                "error": "Conflict",
+                "message": "duplicate id",
+                "details": { "id": 1 }
+            })
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use rocket::response::stream::{Event as SseEvent, EventStream};
+use rocket::serde::json::{serde_json::json, Value};
+use rocket::serde::Serialize;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::{self, error::RecvError};
+use rocket::Shutdown;
+use std::collections::{HashMap, HashSet};
+
+use super::id::Ids;
+
+/// 事件接口的路径
+pub const EVENTS_PATH: &str = "/__events";
+
+/// 订阅者处理不及时时最多缓存的事件数量, 超出后丢弃最早的事件
+const CAPACITY: usize = 1024;
+
+/// 一次修改产生的事件
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Event {
+    /// created, updated 或 deleted
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub resource: String,
+    /// 集合中数据的 id, 非集合的资源为 null
+    pub id: Value,
+    /// 新的数据, 删除时为删除前的数据
+    pub value: Value,
+}
+
+/// 数据变化的通知, 广播给所有订阅者, 没有订阅者时事件直接丢弃
+/// 修改单条数据时由 handler 直接发送事件, 替换整个资源时比较修改前后的资源
+#[derive(Clone)]
+pub struct Events {
+    sender: broadcast::Sender<Event>,
+    ids: Ids,
+}
+
+impl Events {
+    pub fn new(ids: Ids) -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Events { sender, ids }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    fn send(&self, kind: &'static str, name: &str, id: &Value, value: &Value) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        let _ = self.sender.send(Event {
+            kind,
+            resource: name.to_string(),
+            id: id.clone(),
+            value: value.clone(),
+        });
+    }
+
+    /// 集合中新增数据, 非集合的资源 id 为 null
+    pub fn created(&self, name: &str, id: &Value, value: &Value) {
+        self.send("created", name, id, value);
+    }
+
+    pub fn updated(&self, name: &str, id: &Value, value: &Value) {
+        self.send("updated", name, id, value);
+    }
+
+    /// value 为删除前的数据
+    pub fn deleted(&self, name: &str, id: &Value, value: &Value) {
+        self.send("deleted", name, id, value);
+    }
+
+    /// 整个资源 name 从 old 变为 new, None 表示资源不存在
+    pub fn changed(&self, name: &str, old: Option<&Value>, new: Option<&Value>) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        for event in self.diff(name, old, new) {
+            let _ = self.sender.send(event);
+        }
+    }
+
+    /// 整个数据库被替换, 如恢复快照或重新加载文件
+    pub fn replaced(&self, old: &HashMap<String, Value>, new: &HashMap<String, Value>) {
+        let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            self.changed(name, old.get(name), new.get(name));
+        }
+    }
+
+    /// 按 id 索引集合中的数据, 没有 id 的数据按内容索引
+    fn index<'v>(&self, items: &'v [Value]) -> (HashMap<String, &'v Value>, HashSet<String>) {
+        let mut by_id = HashMap::new();
+        let mut without_id = HashSet::new();
+        for item in items {
+            match self.ids.key(item) {
+                Some(key) => {
+                    by_id.insert(key, item);
+                }
+                None => {
+                    without_id.insert(item.to_string());
+                }
+            }
+        }
+        (by_id, without_id)
+    }
+
+    /// 集合按 id 比较每条数据, 资源不存在时当作空集合
+    /// 没有 id 的数据按内容比较, 只会产生 created 和 deleted
+    /// 其他资源作为整体比较
+    fn diff(&self, name: &str, old: Option<&Value>, new: Option<&Value>) -> Vec<Event> {
+        let event = |kind, id: &Value, value: &Value| Event {
+            kind,
+            resource: name.to_string(),
+            id: id.clone(),
+            value: value.clone(),
+        };
+        let empty = vec![];
+        let (old_items, new_items) = match (old, new) {
+            (Some(Value::Array(old)), Some(Value::Array(new))) => (old, new),
+            (Some(Value::Array(old)), None) => (old, &empty),
+            (None, Some(Value::Array(new))) => (&empty, new),
+            (old, new) if old == new => return vec![],
+            (Some(old), None) => return vec![event("deleted", &Value::Null, old)],
+            (None, Some(new)) => return vec![event("created", &Value::Null, new)],
+            (Some(_), Some(new)) => return vec![event("updated", &Value::Null, new)],
+            (None, None) => return vec![],
+        };
+
+        let (old_by_id, old_without_id) = self.index(old_items);
+        let (new_by_id, new_without_id) = self.index(new_items);
+
+        let mut events = vec![];
+        for item in new_items {
+            let id = self.ids.get(item);
+            match self.ids.key(item) {
+                Some(key) => match old_by_id.get(&key) {
+                    None => events.push(event("created", id, item)),
+                    Some(old) if *old != item => events.push(event("updated", id, item)),
+                    Some(_) => {}
+                },
+                None if !old_without_id.contains(&item.to_string()) => {
+                    events.push(event("created", id, item))
+                }
+                None => {}
+            }
+        }
+        for item in old_items {
+            let deleted = match self.ids.key(item) {
+                Some(key) => !new_by_id.contains_key(&key),
+                None => !new_without_id.contains(&item.to_string()),
+            };
+            if deleted {
+                events.push(event("deleted", self.ids.get(item), item));
+            }
+        }
+        events
+    }
+}
+
+/// Server-Sent Events, 每个事件的 event 为事件类型, data 为事件的 JSON
+/// 指定 resource 时只推送该资源的事件
+#[rocket::get("/?<resource>")]
+fn events(resource: Option<String>, events: &Events, mut shutdown: Shutdown) -> EventStream![] {
+    let mut receiver = events.subscribe();
+    EventStream! {
+        loop {
+            let event = select! {
+                event = receiver.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(skipped)) => {
+                        yield SseEvent::json(&json!({ "skipped": skipped })).event("lagged");
+                        continue;
+                    }
+                },
+                _ = &mut shutdown => break,
+            };
+            if resource.as_ref().is_some_and(|resource| *resource != event.resource) {
+                continue;
+            }
+            yield SseEvent::json(&event).event(event.kind);
+        }
+    }
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![events]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::id::IdStrategy;
+    use super::*;
+
+    fn kinds(events: &[Event]) -> Vec<(&str, Value)> {
+        events.iter().map(|e| (e.kind, e.id.clone())).collect()
+    }
+
+    #[test]
+    fn diff_resources() {
+        let events = Events::new(Ids::new(String::from("id"), IdStrategy::Increment));
+        let old = json!([{ "id": 1, "title": "a" }, { "id": 2, "title": "b" }]);
+        let new = json!([{ "id": 1, "title": "c" }, { "id": 3, "title": "d" }]);
+        assert_eq!(
+            kinds(&events.diff("posts", Some(&old), Some(&new))),
+            vec![
+                ("updated", json!(1)),
+                ("created", json!(3)),
+                ("deleted", json!(2))
+            ]
+        );
+        assert_eq!(
+            kinds(&events.diff("posts", Some(&old), None)),
+            vec![("deleted", json!(1)), ("deleted", json!(2))]
+        );
+
+        // 没有 id 的数据内容不变时没有事件
+        let old = json!([{ "title": "a" }, { "id": 1 }]);
+        let new = json!([{ "title": "a" }, { "id": "1" }, { "title": "b" }]);
+        assert_eq!(
+            kinds(&events.diff("posts", Some(&old), Some(&new))),
+            vec![("updated", json!("1")), ("created", Value::Null)]
+        );
+
+        let profile = json!({ "name": "typicode" });
+        assert!(events
+            .diff("profile", Some(&profile), Some(&profile))
+            .is_empty());
+        let diff = events.diff("profile", None, Some(&profile));
+        assert_eq!(
+            diff,
+            vec![Event {
+                kind: "created",
+                resource: String::from("profile"),
+                id: Value::Null,
+                value: profile
+            }]
+        );
+    }
+
+    #[test]
+    fn broadcast() {
+        let events = Events::new(Ids::default());
+        // 没有订阅者时不产生事件
+        events.changed("posts", None, Some(&json!([{ "id": 1 }])));
+        let mut receiver = events.subscribe();
+        events.replaced(
+            &HashMap::from([(String::from("posts"), json!([{ "id": 1 }]))]),
+            &HashMap::from([(String::from("profile"), json!({}))]),
+        );
+        assert_eq!(receiver.try_recv().unwrap().kind, "deleted");
+        assert_eq!(receiver.try_recv().unwrap().resource, "profile");
+        assert!(receiver.try_recv().is_err());
+
+        events.updated("posts", &json!(1), &json!({ "id": 1 }));
+        let event = receiver.try_recv().unwrap();
+        assert_eq!((event.kind, event.id), ("updated", json!(1)));
+    }
+}
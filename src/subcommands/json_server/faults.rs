@@ -0,0 +1,233 @@
+use rand::Rng;
+use regex::Regex;
+use rocket::data::Data;
+use rocket::http::{Method, Status};
+use rocket::response::{self, Responder, Response};
+use rocket::route::{self, Handler, Route};
+use rocket::serde::json::{serde_json, Value};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::io::{self, AsyncRead, ReadBuf};
+use rocket::tokio::time;
+use rocket::Request;
+use std::error::Error;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use super::error::error;
+use super::patch::merge_patch;
+use super::rewrite::{load_rules, route_pattern};
+
+/// 单个请求的延迟, 单位毫秒, 会覆盖配置的延迟和随机延迟
+const DELAY_HEADER: &str = "X-Mock-Delay";
+
+/// 优先级高于所有的资源路由, 先执行延迟和故障注入, 没有故障时交给后面的路由处理
+const FAULT_RANK: isize = -100;
+
+/// 延迟和故障配置
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+pub struct Fault {
+    /// 固定延迟, 单位毫秒
+    pub delay: u64,
+    /// 额外的随机延迟上限, 单位毫秒
+    pub jitter: u64,
+    /// 返回错误的概率, 0 到 1
+    pub fail_rate: f64,
+    /// 返回错误时的状态码
+    pub fail_status: u16,
+    /// 断开连接的概率, 0 到 1
+    pub drop_rate: f64,
+}
+
+impl Default for Fault {
+    fn default() -> Self {
+        Fault {
+            delay: 0,
+            jitter: 0,
+            fail_rate: 0.0,
+            fail_status: 500,
+            drop_rate: 0.0,
+        }
+    }
+}
+
+impl Fault {
+    /// 检查概率和状态码是否合法
+    pub fn check(&self) -> Result<(), String> {
+        for rate in [self.fail_rate, self.drop_rate] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(format!("Rate {} is not between 0 and 1", rate));
+            }
+        }
+        if !(400..600).contains(&self.fail_status) {
+            return Err(format!("Fail status {} is not an error", self.fail_status));
+        }
+        Ok(())
+    }
+}
+
+/// 请求的延迟和故障注入
+/// 每个路由可以覆盖全局配置中的部分字段, 按文件中的顺序使用第一个匹配的路由
+#[derive(Clone)]
+pub struct Faults {
+    default: Fault,
+    routes: Arc<Vec<(Regex, Fault)>>,
+}
+
+impl Faults {
+    pub fn new(default: Fault) -> Result<Self, Box<dyn Error>> {
+        default.check()?;
+        Ok(Faults {
+            default,
+            routes: Arc::new(vec![]),
+        })
+    }
+
+    /// 从文件中读取每个路由的配置, 如
+    /// {"/posts/*": {"delay": 1000}, "/comments": {"fail_rate": 0.5, "fail_status": 503}}
+    pub fn load(default: Fault, path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut routes = vec![];
+        for (route, patch) in load_rules::<Value>(path)? {
+            let mut fault = serde_json::to_value(default)?;
+            merge_patch(&mut fault, &patch);
+            let fault: Fault = serde_json::from_value(fault)
+                .map_err(|e| format!("Invalid faults for {}: {}", route, e))?;
+            fault.check()?;
+            routes.push((route_pattern(&route)?, fault));
+        }
+        let mut faults = Faults::new(default)?;
+        faults.routes = Arc::new(routes);
+        Ok(faults)
+    }
+
+    /// 所有方法的路由
+    pub fn routes(&self) -> Vec<Route> {
+        [
+            Method::Get,
+            Method::Post,
+            Method::Put,
+            Method::Patch,
+            Method::Delete,
+            Method::Head,
+            Method::Options,
+        ]
+        .into_iter()
+        .map(|method| Route::ranked(FAULT_RANK, method, "/<_..>", self.clone()))
+        .collect()
+    }
+
+    /// 路径对应的配置
+    fn fault(&self, path: &str) -> &Fault {
+        self.routes
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(path))
+            .map(|(_, fault)| fault)
+            .unwrap_or(&self.default)
+    }
+}
+
+#[rocket::async_trait]
+impl Handler for Faults {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let fault = self.fault(req.uri().path().as_str());
+        let delay = match req.headers().get_one(DELAY_HEADER) {
+            Some(delay) => match delay.parse::<u64>() {
+                Ok(delay) => delay,
+                Err(_) => {
+                    let message = format!("{} must be a number of milliseconds", DELAY_HEADER);
+                    return route::Outcome::from(req, error(Status::BadRequest, message));
+                }
+            },
+            None => fault.delay + rand::thread_rng().gen_range(0..=fault.jitter),
+        };
+        if delay > 0 {
+            time::sleep(Duration::from_millis(delay)).await;
+        }
+
+        let (drop, fail) = {
+            let mut rng = rand::thread_rng();
+            (rng.gen_bool(fault.drop_rate), rng.gen_bool(fault.fail_rate))
+        };
+        if drop {
+            return route::Outcome::from(req, Dropped);
+        }
+        if fail {
+            let status = Status::new(fault.fail_status);
+            return route::Outcome::from(req, error(status, "Injected failure"));
+        }
+        route::Outcome::forward(data, Status::NotFound)
+    }
+}
+
+/// 断开连接
+/// 声明了响应体的长度, 但读取响应体时出错, 客户端收不到任何响应, 连接会被直接关闭
+struct Dropped;
+
+impl<'r> Responder<'r, 'static> for Dropped {
+    fn respond_to(self, _req: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .raw_header("Content-Length", "1")
+            .streamed_body(Dropped)
+            .ok()
+    }
+}
+
+impl AsyncRead for Dropped {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Ready(Err(io::ErrorKind::ConnectionAborted.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::serde::json::serde_json::json;
+
+    #[test]
+    fn route_faults() {
+        let path =
+            std::env::temp_dir().join(format!("json_server_faults_{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            json!({
+                "/posts/*": { "delay": 1000 },
+                "/posts": { "fail_rate": 1, "fail_status": 503 }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let default = Fault {
+            jitter: 50,
+            ..Fault::default()
+        };
+        let faults = Faults::load(default, &path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(faults.fault("/posts/1").delay, 1000);
+        assert_eq!(faults.fault("/posts/1").jitter, 50);
+        assert_eq!(faults.fault("/posts").fail_status, 503);
+        assert_eq!(faults.fault("/posts").fail_rate, 1.0);
+        assert_eq!(faults.fault("/comments"), &default);
+    }
+
+    #[test]
+    fn invalid_fault() {
+        let fault = Fault {
+            fail_rate: 1.5,
+            ..Fault::default()
+        };
+        assert!(Faults::new(fault).is_err());
+        let fault = Fault {
+            fail_status: 200,
+            ..Fault::default()
+        };
+        assert!(Faults::new(fault).is_err());
+    }
+}
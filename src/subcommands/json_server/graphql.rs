@@ -0,0 +1,658 @@
+use async_graphql::dynamic::{
+    Field, FieldFuture, FieldValue, InputValue, Object, ResolverContext, Scalar, Schema,
+    SchemaError, TypeRef,
+};
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{Error, ErrorExtensions, Request};
+use rocket::http::Status;
+use rocket::response::{content::RawHtml, status};
+use rocket::serde::json::{serde_json, Value};
+use rocket::State;
+use std::collections::{BTreeMap, HashSet};
+
+use super::error::{error, JsonBody};
+use super::events::Events;
+use super::id::Ids;
+use super::mount::Base;
+use super::query::{query_items, QueryParams};
+use super::relations::singular;
+use super::schema::Schemas;
+use super::storage::Storage;
+use super::{insert_value, remove_item, update_item, update_value, Db};
+
+/// 对象, 数组等无法推断类型的值
+const JSON: &str = "JSON";
+
+/// 集合查询支持的参数, 与 REST 接口的查询参数相同
+const LIST_ARGS: [(&str, &str); 7] = [
+    ("q", TypeRef::STRING),
+    ("_sort", TypeRef::STRING),
+    ("_order", TypeRef::STRING),
+    ("_page", TypeRef::INT),
+    ("_limit", TypeRef::INT),
+    ("_start", TypeRef::INT),
+    ("_end", TypeRef::INT),
+];
+
+/// GraphQL 中的名称只能包含字母, 数字和 _, 且不能以数字开头
+fn graphql_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match name.chars().next() {
+        Some(c) if c.is_ascii_digit() => format!("_{}", name),
+        _ => name,
+    }
+}
+
+/// 已经使用的名称, async-graphql 中重复的字段名会 panic
+/// 名称重复时加上数字后缀, 如 first-name 和 first_name 转换后为 first_name 和 first_name2
+#[derive(Default)]
+pub struct Names(HashSet<String>);
+
+impl Names {
+    pub fn reserved(names: &[&str]) -> Self {
+        Names(names.iter().map(|name| name.to_string()).collect())
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.0.contains(name)
+    }
+
+    pub fn unique(&mut self, name: String) -> String {
+        let mut unique = name.clone();
+        let mut suffix = 2;
+        while !self.0.insert(unique.clone()) {
+            unique = format!("{}{}", name, suffix);
+            suffix += 1;
+        }
+        unique
+    }
+}
+
+/// 资源对应的类型名, 如 posts => Post, user_profiles => UserProfile
+/// 去掉 _ 后重新处理数字开头, 如 123 => _123, 没有字母和数字时为 Resource
+pub fn type_name(name: &str) -> String {
+    let name: String = graphql_name(&singular(name))
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| part[..1].to_uppercase() + &part[1..])
+        .collect();
+    match name.chars().next() {
+        None => String::from("Resource"),
+        Some(c) if c.is_ascii_digit() => format!("_{}", name),
+        _ => name,
+    }
+}
+
+/// 根据字段所有的值推断类型, 类型不一致或是对象, 数组时为 JSON
+fn infer_type<'v>(values: impl Iterator<Item = &'v Value>) -> &'static str {
+    let mut inferred = None;
+    for value in values {
+        let ty = match value {
+            Value::Null => continue,
+            Value::Bool(_) => TypeRef::BOOLEAN,
+            Value::Number(n) if n.as_i64().is_some_and(|n| i32::try_from(n).is_ok()) => {
+                TypeRef::INT
+            }
+            Value::Number(_) => TypeRef::FLOAT,
+            Value::String(_) => TypeRef::STRING,
+            _ => JSON,
+        };
+        inferred = match inferred {
+            None => Some(ty),
+            Some(prev) if prev == ty => Some(ty),
+            Some(TypeRef::INT) | Some(TypeRef::FLOAT)
+                if ty == TypeRef::INT || ty == TypeRef::FLOAT =>
+            {
+                Some(TypeRef::FLOAT)
+            }
+            Some(_) => Some(JSON),
+        };
+    }
+    inferred.unwrap_or(JSON)
+}
+
+/// 将数据转换为 GraphQL 的值, id 转换为字符串
+fn to_graphql(value: &Value, is_id: bool) -> Result<Option<FieldValue<'static>>, Error> {
+    let value = match value {
+        Value::Null => return Ok(None),
+        Value::Number(n) if is_id => Value::String(n.to_string()),
+        value => value.clone(),
+    };
+    Ok(Some(FieldValue::value(async_graphql::Value::from_json(
+        value,
+    )?)))
+}
+
+/// 将 REST 接口的错误转换为 GraphQL 的错误, 状态码放在 extensions 中
+fn to_error(err: status::Custom<Value>) -> Error {
+    let message = err.1["message"].as_str().unwrap_or_default().to_string();
+    Error::new(message).extend_with(|_, extensions| extensions.set("status", err.0.code))
+}
+
+/// 参数转换为查询参数, 与 REST 接口的查询参数相同
+fn query_params(ctx: &ResolverContext, name: &str) -> QueryParams {
+    let pairs: Vec<(String, String)> = ctx
+        .args
+        .iter()
+        .map(|(name, value)| {
+            let value = match value.as_value() {
+                async_graphql::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            (name.to_string(), value)
+        })
+        .collect();
+    let pairs: Vec<(&str, &str)> = pairs
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    QueryParams::from_pairs(&format!("/{}", name), &pairs)
+}
+
+/// 参数中的 JSON 数据
+fn data_arg(ctx: &ResolverContext) -> Result<Value, Error> {
+    Ok(ctx.args.try_get("data")?.as_value().clone().into_json()?)
+}
+
+/// 为资源生成对象类型, 字段为所有数据中出现过的字段, 集合的类型总是包含 id 字段
+fn object_type(ty: &str, items: &[&Value], ids: &Ids, collection: bool) -> Object {
+    let mut fields: BTreeMap<&String, Vec<&Value>> = BTreeMap::new();
+    for item in items {
+        if let Value::Object(item) = item {
+            for (key, value) in item {
+                fields.entry(key).or_default().push(value);
+            }
+        }
+    }
+    let mut object = Object::new(ty);
+    let mut names = Names::default();
+    if collection && !fields.contains_key(&ids.field) {
+        let field = names.unique(graphql_name(&ids.field));
+        object = object.field(leaf_field(&field, &ids.field, TypeRef::ID, true));
+    }
+    for (key, values) in fields {
+        let is_id = collection && *key == ids.field;
+        let ty = if is_id {
+            TypeRef::ID
+        } else {
+            infer_type(values.into_iter())
+        };
+        let field = names.unique(graphql_name(key));
+        object = object.field(leaf_field(&field, key, ty, is_id));
+    }
+    object
+}
+
+/// 从父级数据中取出字段的值
+fn leaf_field(field: &str, key: &str, ty: &str, is_id: bool) -> Field {
+    let key = key.to_string();
+    Field::new(field, TypeRef::named(ty), move |ctx| {
+        let key = key.clone();
+        FieldFuture::new(async move {
+            let item = ctx.parent_value.try_downcast_ref::<Value>()?;
+            to_graphql(&item[&key], is_id)
+        })
+    })
+}
+
+/// 根据当前的数据生成 schema
+/// 集合生成列表查询 posts, id 查询 post, 以及 createPost, updatePost, deletePost
+/// 单复数相同或 id 查询与其他查询重名时 id 查询为 staffById
+/// 对象生成查询 profile 和 updateProfile, 空对象和其他数据生成 JSON 类型的查询
+fn build_schema(
+    db: &Db,
+    ids: &Ids,
+    schemas: &Schemas,
+    storage: &Storage,
+    events: &Events,
+) -> Result<Schema, SchemaError> {
+    let mut query = Object::new("Query").field(Field::new("_db", TypeRef::named_nn(JSON), |ctx| {
+        FieldFuture::new(async move {
+            let db = ctx.data::<Db>()?.read();
+            Ok(Some(FieldValue::value(async_graphql::Value::from_json(
+                serde_json::to_value(&*db)?,
+            )?)))
+        })
+    }));
+    let mut mutation = Object::new("Mutation");
+    let mut has_mutation = false;
+    let mut types = vec![];
+    let mut fields = Names::reserved(&["_db"]);
+    let mut type_names = Names::reserved(&[
+        "Query",
+        "Mutation",
+        JSON,
+        TypeRef::STRING,
+        TypeRef::INT,
+        TypeRef::FLOAT,
+        TypeRef::BOOLEAN,
+        TypeRef::ID,
+    ]);
+
+    let data = db.read();
+    let mut names: Vec<&String> = data.keys().collect();
+    names.sort();
+    for name in names {
+        let field_name = fields.unique(graphql_name(name));
+        match &data[name] {
+            Value::Array(items) => {
+                let type_name = type_names.unique(type_name(name));
+                let items: Vec<&Value> = items.iter().collect();
+                types.push(object_type(&type_name, &items, ids, true));
+                let item = graphql_name(&singular(name));
+                let item = if item == field_name || fields.contains(&item) {
+                    fields.unique(format!("{}ById", item))
+                } else {
+                    fields.unique(item)
+                };
+                query = query
+                    .field(list_field(name, &field_name, &type_name, &items, ids))
+                    .field(item_field(name, &item, &type_name));
+                mutation = mutation
+                    .field(create_field(name, &type_name))
+                    .field(update_item_field(name, &type_name))
+                    .field(delete_field(name, &type_name));
+                has_mutation = true;
+            }
+            Value::Object(object) => {
+                let type_name = type_names.unique(type_name(name));
+                // 没有字段的对象类型无法生成 schema, 作为 JSON 处理
+                let ty = if object.is_empty() {
+                    TypeRef::named(JSON)
+                } else {
+                    types.push(object_type(&type_name, &[&data[name]], ids, false));
+                    TypeRef::named(&type_name)
+                };
+                query = query.field(value_field(name, &field_name, ty.clone()));
+                mutation = mutation.field(update_value_field(name, &type_name, ty));
+                has_mutation = true;
+            }
+            _ => query = query.field(value_field(name, &field_name, TypeRef::named(JSON))),
+        }
+    }
+    drop(data);
+
+    let mut builder = Schema::build("Query", has_mutation.then_some("Mutation"), None::<&str>)
+        .register(Scalar::new(JSON))
+        .register(query)
+        .data(db.clone())
+        .data(ids.clone())
+        .data(schemas.clone())
+        .data(storage.clone())
+        .data(events.clone());
+    if has_mutation {
+        builder = builder.register(mutation);
+    }
+    for object in types {
+        builder = builder.register(object);
+    }
+    builder.finish()
+}
+
+/// 列表查询, 每个字段都可以作为相等过滤条件
+fn list_field(name: &str, field: &str, ty: &str, items: &[&Value], ids: &Ids) -> Field {
+    let mut keys: Vec<&String> = items
+        .iter()
+        .filter_map(|item| item.as_object())
+        .flat_map(|item| item.keys())
+        .collect();
+    keys.sort();
+    keys.dedup();
+
+    let name = name.to_string();
+    let mut list = Field::new(field, TypeRef::named_nn_list_nn(ty), move |ctx| {
+        let name = name.clone();
+        FieldFuture::new(async move {
+            let params = query_params(&ctx, &name);
+            let db = ctx.data::<Db>()?.read();
+            let items = match db.get(&name) {
+                Some(Value::Array(items)) => items.clone(),
+                _ => vec![],
+            };
+            let res = query_items(items, &params).map_err(to_error)?;
+            let items = match res.body {
+                Value::Array(items) => items,
+                _ => vec![],
+            };
+            Ok(Some(FieldValue::list(
+                items.into_iter().map(FieldValue::owned_any),
+            )))
+        })
+    });
+    for key in keys {
+        // 参数名称需要与字段名称相同, 无法转换的字段不能作为过滤条件
+        if graphql_name(key) == *key && !key.starts_with('_') && key != "q" {
+            list = list.argument(InputValue::new(key, TypeRef::named(TypeRef::STRING)));
+        }
+    }
+    if !ids.field.starts_with('_') && graphql_name(&ids.field) == ids.field {
+        list = list.argument(InputValue::new(&ids.field, TypeRef::named(TypeRef::STRING)));
+    }
+    for (arg, ty) in LIST_ARGS {
+        list = list.argument(InputValue::new(arg, TypeRef::named(ty)));
+    }
+    list
+}
+
+/// 按 id 查询
+fn item_field(name: &str, field: &str, ty: &str) -> Field {
+    let name = name.to_string();
+    Field::new(field, TypeRef::named(ty), move |ctx| {
+        let name = name.clone();
+        FieldFuture::new(async move {
+            let id = ctx.args.try_get("id")?.string()?.to_string();
+            let ids = ctx.data::<Ids>()?;
+            let db = ctx.data::<Db>()?.read();
+            let item = match db.get(&name) {
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .find(|item| ids.is_value_equal_str(item, &id))
+                    .cloned(),
+                _ => None,
+            };
+            Ok(item.map(FieldValue::owned_any))
+        })
+    })
+    .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID)))
+}
+
+/// 对象或其他数据的查询
+fn value_field(name: &str, field: &str, ty: TypeRef) -> Field {
+    let name = name.to_string();
+    let is_object = ty.type_name() != JSON;
+    Field::new(field, ty, move |ctx| {
+        let name = name.clone();
+        FieldFuture::new(async move {
+            let db = ctx.data::<Db>()?.read();
+            match db.get(&name) {
+                Some(value) if is_object => Ok(Some(FieldValue::owned_any(value.clone()))),
+                Some(value) => to_graphql(value, false),
+                None => Ok(None),
+            }
+        })
+    })
+}
+
+fn create_field(name: &str, ty: &str) -> Field {
+    let name = name.to_string();
+    Field::new(format!("create{}", ty), TypeRef::named_nn(ty), move |ctx| {
+        let name = name.clone();
+        FieldFuture::new(async move {
+            let data = data_arg(&ctx)?;
+            let (ids, schemas, storage, events) = (
+                ctx.data::<Ids>()?,
+                ctx.data::<Schemas>()?,
+                ctx.data::<Storage>()?,
+                ctx.data::<Events>()?,
+            );
+            let mut db = ctx.data::<Db>()?.write();
+            let item = insert_value(&mut db, ids, schemas, storage, events, &name, data)
+                .map_err(to_error)?;
+            Ok(Some(FieldValue::owned_any(item)))
+        })
+    })
+    .argument(InputValue::new("data", TypeRef::named_nn(JSON)))
+}
+
+fn update_item_field(name: &str, ty: &str) -> Field {
+    let name = name.to_string();
+    Field::new(format!("update{}", ty), TypeRef::named_nn(ty), move |ctx| {
+        let name = name.clone();
+        FieldFuture::new(async move {
+            let id = ctx.args.try_get("id")?.string()?.to_string();
+            let data = data_arg(&ctx)?;
+            let (ids, schemas, storage, events) = (
+                ctx.data::<Ids>()?,
+                ctx.data::<Schemas>()?,
+                ctx.data::<Storage>()?,
+                ctx.data::<Events>()?,
+            );
+            let mut db = ctx.data::<Db>()?.write();
+            let item = update_item(&mut db, ids, schemas, storage, events, &name, &id, data)
+                .map_err(to_error)?;
+            Ok(Some(FieldValue::owned_any(item)))
+        })
+    })
+    .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID)))
+    .argument(InputValue::new("data", TypeRef::named_nn(JSON)))
+}
+
+fn delete_field(name: &str, ty: &str) -> Field {
+    let name = name.to_string();
+    Field::new(format!("delete{}", ty), TypeRef::named_nn(ty), move |ctx| {
+        let name = name.clone();
+        FieldFuture::new(async move {
+            let id = ctx.args.try_get("id")?.string()?.to_string();
+            let (ids, storage, events) = (
+                ctx.data::<Ids>()?,
+                ctx.data::<Storage>()?,
+                ctx.data::<Events>()?,
+            );
+            let mut db = ctx.data::<Db>()?.write();
+            let item = remove_item(&mut db, ids, storage, events, &name, &id).map_err(to_error)?;
+            Ok(Some(FieldValue::owned_any(item)))
+        })
+    })
+    .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID)))
+}
+
+fn update_value_field(name: &str, type_name: &str, ty: TypeRef) -> Field {
+    let name = name.to_string();
+    let is_object = ty.type_name() != JSON;
+    let ty = TypeRef::named_nn(ty.type_name());
+    Field::new(format!("update{}", type_name), ty, move |ctx| {
+        let name = name.clone();
+        FieldFuture::new(async move {
+            let data = data_arg(&ctx)?;
+            let (schemas, storage, events) = (
+                ctx.data::<Schemas>()?,
+                ctx.data::<Storage>()?,
+                ctx.data::<Events>()?,
+            );
+            let mut db = ctx.data::<Db>()?.write();
+            let value =
+                update_value(&mut db, schemas, storage, events, &name, data).map_err(to_error)?;
+            match is_object {
+                true => Ok(Some(FieldValue::owned_any(value))),
+                false => to_graphql(&value, false),
+            }
+        })
+    })
+    .argument(InputValue::new("data", TypeRef::named_nn(JSON)))
+}
+
+/// 执行 GraphQL 请求, schema 按请求时的数据生成, 新建的资源也可以查询
+#[rocket::post("/graphql", data = "<request>")]
+pub async fn graphql(
+    request: JsonBody<Request>,
+    db: &Db,
+    ids: &State<Ids>,
+    schemas: &State<Schemas>,
+    storage: &Storage,
+    events: &Events,
+) -> Result<Value, status::Custom<Value>> {
+    let schema = build_schema(db, ids, schemas, storage, events)
+        .map_err(|e| error(Status::InternalServerError, e.to_string()))?;
+    let response = schema.execute(request.into_inner()).await;
+    Ok(serde_json::to_value(response).unwrap())
+}
+
+/// GraphiQL 页面
+#[rocket::get("/graphql")]
+pub fn graphiql(base: Base) -> RawHtml<String> {
+    let endpoint = format!("{}/graphql", base);
+    RawHtml(GraphiQLSource::build().endpoint(&endpoint).finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::serde::json::serde_json::json;
+    use rocket::tokio::runtime::Runtime;
+    use std::collections::HashMap;
+
+    fn execute(db: &Db, query: &str) -> Value {
+        let schema = build_schema(
+            db,
+            &Ids::default(),
+            &Schemas::default(),
+            &Storage::read_only(),
+            &Events::new(Ids::default()),
+        )
+        .unwrap();
+        let response = Runtime::new()
+            .unwrap()
+            .block_on(schema.execute(Request::new(query)));
+        serde_json::to_value(response).unwrap()
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(type_name("posts"), "Post");
+        assert_eq!(type_name("user-profiles"), "UserProfile");
+        assert_eq!(type_name("123"), "_123");
+        assert_eq!(type_name("_"), "Resource");
+        assert_eq!(type_name("__"), "Resource");
+        assert_eq!(graphql_name("1st"), "_1st");
+        assert_eq!(infer_type([json!(1), json!(2.5)].iter()), TypeRef::FLOAT);
+        assert_eq!(infer_type([json!(1), json!("a")].iter()), JSON);
+        assert_eq!(
+            infer_type([json!(null), json!(true)].iter()),
+            TypeRef::BOOLEAN
+        );
+    }
+
+    #[test]
+    fn query_and_mutation() {
+        let db: HashMap<String, Value> = serde_json::from_value(json!({
+            "posts": [
+                { "id": 1, "title": "json-server", "views": 10, "tags": ["a"] },
+                { "id": 2, "title": "rust", "views": 20 }
+            ],
+            "profile": { "name": "typicode" },
+            "version": "1.0"
+        }))
+        .unwrap();
+        let db = Db::new(db);
+
+        let res = execute(
+            &db,
+            r#"{ posts(_sort: "views", _order: "desc") { id title tags }
+                 post(id: "1") { title views }
+                 profile { name }
+                 version }"#,
+        );
+        assert_eq!(
+            res["data"],
+            json!({
+                "posts": [
+                    { "id": "2", "title": "rust", "tags": null },
+                    { "id": "1", "title": "json-server", "tags": ["a"] }
+                ],
+                "post": { "title": "json-server", "views": 10 },
+                "profile": { "name": "typicode" },
+                "version": "1.0"
+            })
+        );
+        let res = execute(&db, r#"{ posts(title: "rust") { id } }"#);
+        assert_eq!(res["data"]["posts"], json!([{ "id": "2" }]));
+
+        let res = execute(
+            &db,
+            r#"mutation { createPost(data: { title: "graphql" }) { id }
+                          updatePost(id: "1", data: { views: 11 }) { views }
+                          deletePost(id: "2") { title }
+                          updateProfile(data: { name: "rust" }) { name } }"#,
+        );
+        assert_eq!(
+            res["data"],
+            json!({
+                "createPost": { "id": "3" },
+                "updatePost": { "views": 11 },
+                "deletePost": { "title": "rust" },
+                "updateProfile": { "name": "rust" }
+            })
+        );
+        assert_eq!(db.read()["posts"].as_array().unwrap().len(), 2);
+
+        let res = execute(&db, r#"mutation { deletePost(id: "9") { id } }"#);
+        assert_eq!(res["errors"][0]["message"], "Item 9 not found in posts");
+        assert_eq!(res["errors"][0]["extensions"]["status"], 404);
+    }
+
+    fn db(value: Value) -> Db {
+        Db::new(serde_json::from_value(value).unwrap())
+    }
+
+    #[test]
+    fn same_singular_and_plural() {
+        let db = db(json!({ "staff": [{ "id": 1, "name": "a" }] }));
+        let res = execute(&db, r#"{ staff { name } staffById(id: "1") { name } }"#);
+        assert_eq!(
+            res["data"],
+            json!({ "staff": [{ "name": "a" }], "staffById": { "name": "a" } })
+        );
+    }
+
+    #[test]
+    fn sanitized_keys() {
+        let db = db(json!({ "users": [{ "id": 1, "first-name": "a", "first_name": "b" }] }));
+        let res = execute(&db, r#"{ user(id: "1") { first_name first_name2 } }"#);
+        assert_eq!(
+            res["data"]["user"],
+            json!({ "first_name": "a", "first_name2": "b" })
+        );
+
+        // 类型名不能以数字开头, 也不能为空
+        let db = self::db(json!({ "123": [{ "id": 1 }], "_": { "name": "a" } }));
+        let res = execute(
+            &db,
+            r#"{ _123 { id } _ { name } __schema { types { name } } }"#,
+        );
+        assert_eq!(res["data"]["_"], json!({ "name": "a" }));
+        let types = res["data"]["__schema"]["types"].as_array().unwrap();
+        for name in ["_123", "Resource"] {
+            assert!(types.iter().any(|ty| ty["name"] == json!(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn object_and_collection() {
+        let db = db(json!({
+            "profile": { "name": "a" },
+            "profiles": [{ "id": 1, "name": "b" }]
+        }));
+        let res = execute(
+            &db,
+            r#"{ profile { name } profiles { name } profileById(id: "1") { name } }"#,
+        );
+        assert_eq!(
+            res["data"],
+            json!({
+                "profile": { "name": "a" },
+                "profiles": [{ "name": "b" }],
+                "profileById": { "name": "b" }
+            })
+        );
+        let res = execute(
+            &db,
+            r#"mutation { updateProfile(data: { age: 1 }) { name } createProfile2(data: {}) { id } }"#,
+        );
+        assert_eq!(
+            res["data"],
+            json!({ "updateProfile": { "name": "a" }, "createProfile2": { "id": "2" } })
+        );
+    }
+
+    #[test]
+    fn empty_object() {
+        let db = db(json!({ "profile": {} }));
+        let res = execute(&db, "{ profile }");
+        assert_eq!(res["data"], json!({ "profile": {} }));
+        let res = execute(&db, r#"mutation { updateProfile(data: { name: "a" }) }"#);
+        assert_eq!(res["data"], json!({ "updateProfile": { "name": "a" } }));
+    }
+}
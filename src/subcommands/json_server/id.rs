@@ -0,0 +1,167 @@
+use clap::ValueEnum;
+use rocket::serde::json::Value;
+use ulid::Ulid;
+use uuid::Uuid;
+
+/// 新数据 id 的生成方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum IdStrategy {
+    /// Max numeric id + 1
+    Increment,
+    /// Random UUID v4
+    Uuid,
+    /// ULID, sortable by creation time
+    Ulid,
+    /// 21 characters nanoid
+    Nanoid,
+}
+
+/// id 字段名称和生成方式
+#[derive(Clone)]
+pub struct Ids {
+    /// id 字段名称, 默认为 id
+    pub field: String,
+    pub strategy: IdStrategy,
+}
+
+impl Default for Ids {
+    fn default() -> Self {
+        Ids {
+            field: String::from("id"),
+            strategy: IdStrategy::Increment,
+        }
+    }
+}
+
+impl Ids {
+    pub fn new(field: String, strategy: IdStrategy) -> Self {
+        Ids { field, strategy }
+    }
+
+    /// 获取数据中的 id
+    pub fn get<'v>(&self, item: &'v Value) -> &'v Value {
+        &item[&self.field]
+    }
+
+    /// 为数组中的新数据生成 id
+    /// 自增时取原数组中最大的数字 id + 1, 空数组从 1 开始
+    pub fn generate(&self, items: &[Value]) -> Value {
+        match self.strategy {
+            IdStrategy::Increment => {
+                let max_id = items
+                    .iter()
+                    .map(|item| self.get_value_id(item))
+                    .max()
+                    .unwrap_or(0);
+                Value::from(max_id + 1)
+            }
+            IdStrategy::Uuid => Value::String(Uuid::new_v4().to_string()),
+            IdStrategy::Ulid => Value::String(Ulid::new().to_string()),
+            IdStrategy::Nanoid => Value::String(nanoid::nanoid!()),
+        }
+    }
+
+    /// 获取 Value 中的 id 并转化为数字, id 可能是字符串或数字或其他类型
+    /// 无法转化为数字时返回 0
+    pub fn get_value_id(&self, item: &Value) -> u64 {
+        let id = self.get(item);
+        if let Some(str_id) = id.as_str() {
+            // print_debug("str_id", str_id);
+            str_id.parse::<u64>().unwrap_or(0)
+        } else {
+            id.as_u64().unwrap_or_default()
+        }
+    }
+
+    /// 数据的 id 转换为字符串, 用于按 id 查找, 1 和 "1" 相同, 没有 id 或不是字符串和数字时返回 None
+    pub fn key(&self, item: &Value) -> Option<String> {
+        match self.get(item) {
+            Value::String(id) => Some(id.clone()),
+            id => id.as_u64().map(|id| id.to_string()),
+        }
+    }
+
+    /// 比较 Value 的 id 是否与指定 id 相等，id 可能是字符串或数字或其他类型
+    pub fn is_value_equal_str(&self, item: &Value, id: &str) -> bool {
+        is_id_equal_str(self.get(item), id)
+    }
+
+    /// 比较两个 Value 的 id 是否相等，id 可能是字符串或数字或其他类型
+    /// TODO: 两个相同的其他类型没有判断
+    pub fn is_value_equal_value(&self, item: &Value, data: &Value) -> bool {
+        let (item_id, data_id) = (self.get(item), self.get(data));
+        if let Some(str_id) = item_id.as_str() {
+            if let Some(data_str_id) = data_id.as_str() {
+                str_id == data_str_id
+            } else if let Some(data_num_id) = data_id.as_u64() {
+                str_id == data_num_id.to_string()
+            } else {
+                false
+            }
+        } else if let Some(num_id) = item_id.as_u64() {
+            if let Some(data_str_id) = data_id.as_str() {
+                num_id.to_string() == data_str_id
+            } else if let Some(data_num_id) = data_id.as_u64() {
+                num_id == data_num_id
+            } else {
+                false
+            }
+        } else {
+            false
+        }
+    }
+}
+
+/// 比较 id 字段的值是否与指定 id 相等，id 可能是字符串或数字或其他类型
+pub fn is_id_equal_str(id_value: &Value, id: &str) -> bool {
+    if let Some(str_id) = id_value.as_str() {
+        // print_debug("str_id", str_id);
+        str_id == id
+    } else if let Some(num_id) = id_value.as_u64() {
+        // print_debug("num_id", num_id);
+        num_id.to_string() == id
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::serde::json::serde_json::json;
+
+    #[test]
+    fn increment() {
+        let ids = Ids::default();
+        assert_eq!(ids.generate(&[]), json!(1));
+        assert_eq!(
+            ids.generate(&[
+                json!({ "id": 2 }),
+                json!({ "id": "7" }),
+                json!({ "id": "abc" })
+            ]),
+            json!(8)
+        );
+    }
+
+    #[test]
+    fn custom_field() {
+        let ids = Ids::new(String::from("_id"), IdStrategy::Increment);
+        let items = [json!({ "_id": 3, "id": 10 })];
+        assert_eq!(ids.generate(&items), json!(4));
+        assert!(ids.is_value_equal_str(&items[0], "3"));
+        assert!(!ids.is_value_equal_str(&items[0], "10"));
+    }
+
+    #[test]
+    fn string_strategies() {
+        let uuid = Ids::new(String::from("id"), IdStrategy::Uuid).generate(&[]);
+        assert!(Uuid::parse_str(uuid.as_str().unwrap()).is_ok());
+
+        let ulid = Ids::new(String::from("id"), IdStrategy::Ulid).generate(&[]);
+        assert!(Ulid::from_string(ulid.as_str().unwrap()).is_ok());
+
+        let nanoid = Ids::new(String::from("id"), IdStrategy::Nanoid).generate(&[]);
+        assert_eq!(nanoid.as_str().unwrap().len(), 21);
+    }
+}
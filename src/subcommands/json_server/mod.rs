@@ -0,0 +1,1050 @@
+use clap::Args;
+use rocket::http::uri::Origin;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::{serde_json, Value};
+use rocket::{catchers, routes, Build, Config, Either, Rocket, State};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{collections::HashMap, error::Error};
+use tokio::runtime::Runtime;
+
+use crate::cli::RunCommand;
+use crate::tools::print_debug;
+use admin::{Snapshots, ADMIN_BASE};
+use db::Db;
+use error::{default_catcher, error, error_with, JsonBody};
+use events::{Events, EVENTS_PATH};
+use faults::{Fault, Faults};
+use id::{IdStrategy, Ids};
+use json_patch::Patch;
+use mount::{Database, Databases, Mount};
+use openapi::DOCS_PATH;
+use patch::{json_patch, merge_patch};
+use proxy::Proxy;
+use query::{query_items, Collection, QueryParams};
+use relations::{foreign_key, populate};
+use rewrite::Rewriter;
+use schema::Schemas;
+use storage::Storage;
+use ui::{Pages, RequestLog, UI_BASE};
+use watch::Watcher;
+
+mod admin;
+mod db;
+mod error;
+mod events;
+mod faults;
+mod graphql;
+mod id;
+mod mount;
+mod openapi;
+mod patch;
+mod proxy;
+mod query;
+mod relations;
+mod rewrite;
+mod schema;
+mod seed;
+mod storage;
+#[cfg(test)]
+mod tests;
+mod ui;
+mod watch;
+
+/// 404
+fn not_found(message: String) -> status::Custom<Value> {
+    error(Status::NotFound, message)
+}
+
+/// 404 原数组中没有当前 id
+fn item_not_found(name: &str, id: &str) -> status::Custom<Value> {
+    print_debug("原数据是否为数组", true);
+    print_debug("原数组中没有当前 id", id);
+    not_found(format!("Item {} not found in {}", id, name))
+}
+
+/// 422 数组中的数据必须是对象, 否则无法设置 id
+fn check_data_is_object(data_value: &Value) -> Result<(), status::Custom<Value>> {
+    if data_value.is_object() {
+        return Ok(());
+    }
+    Err(error_with(
+        Status::UnprocessableEntity,
+        "Request body must be a JSON object",
+        data_value.clone(),
+    ))
+}
+
+/// 422 集合中的数据必须是有 id 的对象, 用于检查 JSON Patch 修改后的集合
+fn check_items_have_id(ids: &Ids, items: &[Value]) -> Result<(), status::Custom<Value>> {
+    for item in items {
+        check_data_is_object(item)?;
+        if ids.get(item).is_null() {
+            return Err(error_with(
+                Status::UnprocessableEntity,
+                format!("Item must have an {} field", ids.field),
+                item.clone(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 422 写入的文件格式不支持这些数据, 如 TOML 不支持 null
+/// 500 上一次写入文件失败, 写入成功前不再接受修改
+fn check_storable(storage: &Storage, data_value: &Value) -> Result<(), status::Custom<Value>> {
+    if let Some(message) = storage.write_error() {
+        return Err(error(Status::InternalServerError, message));
+    }
+    storage
+        .check(data_value)
+        .map_err(|message| error(Status::UnprocessableEntity, message))
+}
+
+/// 新建数据的地址, 如 POST /posts 返回 /posts/1
+/// depth 为请求路径中需要去掉的段数, 剩下的部分作为前缀
+fn location(origin: &Origin, depth: usize, name: &str, id: &Value) -> String {
+    let path = origin.path().as_str().trim_end_matches('/');
+    let prefix = path.rsplitn(depth + 1, '/').last().unwrap_or("");
+    match id {
+        Value::String(id) => format!("{}/{}/{}", prefix, name, id),
+        Value::Null => format!("{}/{}", prefix, name),
+        id => format!("{}/{}/{}", prefix, name, id),
+    }
+}
+
+/// 检查原数据是否存在
+fn check_db_value_is_empty<'v>(
+    db_value: Option<&'v Value>,
+    name: &str,
+) -> Result<&'v Value, status::Custom<Value>> {
+    match db_value {
+        Some(db_value) => Ok(db_value),
+        None => {
+            print_debug("没有查找到", name);
+            Err(not_found(format!("Resource {} not found", name)))
+        }
+    }
+}
+
+/// 检查原数据是否为数组, is_array 为 true 时原数据不能是数组, 为 false 时原数据必须是数组
+/// 类型不符合时返回 400
+fn check_db_value_is_array<'v>(
+    db_value: Option<&'v Value>,
+    name: &str,
+    is_array: bool,
+) -> Result<&'v Value, status::Custom<Value>> {
+    let db_value = check_db_value_is_empty(db_value, name)?;
+    if db_value.is_array() == is_array {
+        print_debug("查找到", name);
+        print_debug("原数据是否为数组", db_value.is_array());
+        let message = if is_array {
+            format!(
+                "Resource {} is a collection, use /{}/<id> instead",
+                name, name
+            )
+        } else {
+            format!("Resource {} is not a collection", name)
+        };
+        return Err(error(Status::BadRequest, message));
+    }
+    Ok(db_value)
+}
+
+/// 插入数据并写入到文件中, 事件由调用方发送
+fn inset_and_write(
+    db: &mut HashMap<String, Value>,
+    storage: &Storage,
+    name: &str,
+    data_value: Value,
+) {
+    db.insert(name.to_string(), data_value);
+    // print_debug("db", db.clone());
+    storage.write(db);
+}
+
+/// 删除整个资源并写入到文件中
+fn remove_and_write(
+    db: &mut HashMap<String, Value>,
+    storage: &Storage,
+    events: &Events,
+    name: &str,
+) {
+    if let Some(db_value) = db.remove(name) {
+        events.changed(name, Some(&db_value), None);
+    }
+    storage.write(db);
+}
+
+/// 返回整个数据库
+#[rocket::get("/db")]
+fn get_db(db: &Db) -> Value {
+    let db = db.read();
+    serde_json::to_value(&*db).unwrap()
+}
+
+/// 查找 name 属性, 如果不存在返回 Err
+/// 如果存在, 但是数据不是数组, 返回原数据
+/// 如果存在, 且数据是数组, 按查询参数过滤, 排序和分页后返回
+/// 支持 `_embed` 和 `_expand` 填充关联数据
+#[rocket::get("/<name>")]
+fn get_name(
+    name: &str,
+    params: QueryParams,
+    db: &Db,
+    ids: &State<Ids>,
+) -> Result<Collection, status::Custom<Value>> {
+    let db = db.read();
+    let db_value = db.get(name);
+    match db_value {
+        Some(Value::Array(items)) => {
+            let mut res = query_items(items.clone(), &params)?;
+            if let Value::Array(items) = &mut res.body {
+                items
+                    .iter_mut()
+                    .for_each(|item| populate(&db, ids, name, item, &params));
+            }
+            Ok(res)
+        }
+        Some(db_value) => Ok(Collection {
+            body: db_value.clone(),
+            total: None,
+            link: None,
+        }),
+        None => Err(not_found(format!("Resource {} not found", name))),
+    }
+}
+
+/// 查找 name 属性, 如果不存在返回 Err
+/// 如果存在, 但是数据不是数组, 返回 Err
+/// 如果存在, 且数据是数组, 那么查找 id, 如果不存在返回 Err
+/// 返回查找到的数据, 支持 `_embed` 和 `_expand` 填充关联数据
+#[rocket::get("/<name>/<id>")]
+fn get_name_id(
+    name: &str,
+    id: &str,
+    params: QueryParams,
+    db: &Db,
+    ids: &State<Ids>,
+) -> Result<Value, status::Custom<Value>> {
+    let db = db.read();
+    let db_value = db.get(name);
+    let db_value = check_db_value_is_array(db_value, name, false)?;
+    let db_value = db_value.as_array().unwrap();
+    // 从数组中查找 id
+    let res_value = db_value
+        .iter()
+        .find(|item| ids.is_value_equal_str(item, id));
+    match res_value {
+        Some(res_value) => {
+            let mut res_value = res_value.clone();
+            populate(&db, ids, name, &mut res_value, &params);
+            Ok(res_value)
+        }
+        None => Err(item_not_found(name, id)),
+    }
+}
+
+/// 查找父资源中的 id, 返回父资源中 id 字段的原始值
+fn find_parent_id(
+    db: &HashMap<String, Value>,
+    ids: &Ids,
+    name: &str,
+    id: &str,
+) -> Result<Value, status::Custom<Value>> {
+    let db_value = check_db_value_is_array(db.get(name), name, false)?;
+    db_value
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|item| ids.is_value_equal_str(item, id))
+        .map(|item| ids.get(item).clone())
+        .ok_or_else(|| item_not_found(name, id))
+}
+
+/// 查找父资源中的 id, 返回子资源中外键等于 id 的数据
+/// 如 `/posts/1/comments` 返回 comments 中 postId 为 1 的数据
+fn find_children(
+    db: &HashMap<String, Value>,
+    ids: &Ids,
+    name: &str,
+    id: &str,
+    nested: &str,
+) -> Result<Vec<Value>, status::Custom<Value>> {
+    find_parent_id(db, ids, name, id)?;
+    check_db_value_is_array(db.get(nested), nested, false)?;
+    Ok(relations::children(db, name, nested, id).cloned().collect())
+}
+
+/// 查找 name 属性, 如果不存在新建一个数组
+/// 如果存在, 但是数据不是数组, 直接替换原数据
+/// 如果存在, 且数据是数组, 需要判断 data 中的 id 是否存在
+/// 如果 data 中没有 id, 那么按 id 生成方式生成新的 id 并插入, 默认为原数组中的最大 id + 1
+/// 如果 data 中有 id, 那么判断原数组中对应 id 是否存在
+/// 如果原数组中对应 id 不存在, 那么插入新数据
+/// 如果原数组中对应 id 存在, 那么插入失败, 返回 409
+/// 返回 201 和插入的数据, Location 为新数据的地址
+#[rocket::post("/<name>", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
+fn post_name(
+    name: &str,
+    data: JsonBody<Value>,
+    origin: &Origin,
+    db: &Db,
+    ids: &State<Ids>,
+    schemas: &State<Schemas>,
+    storage: &Storage,
+    events: &Events,
+) -> Result<status::Created<Value>, status::Custom<Value>> {
+    let mut db = db.write();
+    let data_value = insert_value(
+        &mut db,
+        ids,
+        schemas,
+        storage,
+        events,
+        name,
+        data.into_inner(),
+    )?;
+    let location = location(origin, 1, name, ids.get(&data_value));
+    Ok(status::Created::new(location).body(data_value))
+}
+
+/// 查找父资源和子资源, 如果不存在返回 Err
+/// 返回子资源中外键等于 id 的数据, 支持与 `/<name>` 相同的查询参数
+#[rocket::get("/<name>/<id>/<nested>")]
+fn get_name_id_nested(
+    name: &str,
+    id: &str,
+    nested: &str,
+    params: QueryParams,
+    db: &Db,
+    ids: &State<Ids>,
+) -> Result<Collection, status::Custom<Value>> {
+    let db = db.read();
+    let items = find_children(&db, ids, name, id, nested)?;
+    let mut res = query_items(items, &params)?;
+    if let Value::Array(items) = &mut res.body {
+        items
+            .iter_mut()
+            .for_each(|item| populate(&db, ids, nested, item, &params));
+    }
+    Ok(res)
+}
+
+/// 查找父资源, 如果不存在返回 Err
+/// 将 data 中的外键设置为父资源的 id, 然后插入到子资源中, 子资源不存在时会新建
+/// 如 `/posts/1/comments` 插入的数据 postId 为 1
+/// 返回 201 和插入的数据, Location 为新数据的地址, 如 `/comments/1`
+#[rocket::post("/<name>/<id>/<nested>", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
+fn post_name_id_nested(
+    name: &str,
+    id: &str,
+    nested: &str,
+    data: JsonBody<Value>,
+    origin: &Origin,
+    db: &Db,
+    ids: &State<Ids>,
+    schemas: &State<Schemas>,
+    storage: &Storage,
+    events: &Events,
+) -> Result<status::Created<Value>, status::Custom<Value>> {
+    let mut data_value = data.into_inner();
+    check_data_is_object(&data_value)?;
+    let mut db = db.write();
+    data_value[foreign_key(name)] = find_parent_id(&db, ids, name, id)?;
+    let data_value = insert_value(&mut db, ids, schemas, storage, events, nested, data_value)?;
+    let location = location(origin, 3, nested, ids.get(&data_value));
+    Ok(status::Created::new(location).body(data_value))
+}
+
+/// 插入数据到 name 属性中, 规则见 post_name
+fn insert_value(
+    db: &mut HashMap<String, Value>,
+    ids: &Ids,
+    schemas: &Schemas,
+    storage: &Storage,
+    events: &Events,
+    name: &str,
+    mut data_value: Value,
+) -> Result<Value, status::Custom<Value>> {
+    let mut db_value: Vec<Value> = match db.get(name) {
+        Some(Value::Array(db_value)) => db_value.clone(),
+        // 原数据不是数组, 直接更新原数据
+        Some(_) => {
+            schemas.validate(name, &data_value)?;
+            check_storable(storage, &data_value)?;
+            events.updated(name, &Value::Null, &data_value);
+            inset_and_write(db, storage, name, data_value.clone());
+            return Ok(data_value);
+        }
+        // 原数据不存在, 新建一个数组
+        None => {
+            print_debug("新建资源", name);
+            vec![]
+        }
+    };
+    check_data_is_object(&data_value)?;
+
+    // 原数据是数组, 且 data 中没有 id
+    if ids.get(&data_value).is_null() {
+        // 按 id 生成方式生成新的 id, 自增时获取原数组中的最大 id, 然后 +1
+        data_value[&ids.field] = ids.generate(&db_value);
+        schemas.validate(name, &data_value)?;
+        check_storable(storage, &data_value)?;
+        events.created(name, ids.get(&data_value), &data_value);
+        db_value.push(data_value.clone());
+        inset_and_write(db, storage, name, serde_json::to_value(db_value).unwrap());
+        return Ok(data_value);
+    }
+
+    // 原数据是数组, 且 data 中有 id
+    schemas.validate(name, &data_value)?;
+    check_storable(storage, &data_value)?;
+    // 判断原数组中对应 id 是否存在
+    let exists_value = db_value
+        .iter()
+        .find(|item| ids.is_value_equal_value(item, &data_value));
+    match exists_value {
+        Some(_) => {
+            // id 存在, 插入失败
+            print_debug("原数据是数组, 且存在 data 中相同 id", ids.get(&data_value));
+            Err(error_with(
+                Status::Conflict,
+                format!("Insert failed, duplicate id {}", ids.get(&data_value)),
+                data_value,
+            ))
+        }
+        None => {
+            // id 不存在, 那么插入新数据
+            events.created(name, ids.get(&data_value), &data_value);
+            db_value.push(data_value.clone());
+            inset_and_write(db, storage, name, serde_json::to_value(db_value).unwrap());
+            Ok(data_value)
+        }
+    }
+}
+
+/// 查找 name 属性, 如果不存在, 以 data 新建资源, 返回 201
+/// 如果存在, 但是数据是数组, 返回 Err
+/// 如果存在, 且数据不是数组, 替换原数据
+#[rocket::put("/<name>", data = "<data>")]
+fn put_name(
+    name: &str,
+    data: JsonBody<Value>,
+    origin: &Origin,
+    db: &Db,
+    schemas: &State<Schemas>,
+    storage: &Storage,
+    events: &Events,
+) -> Result<Either<status::Created<Value>, Value>, status::Custom<Value>> {
+    let data_value = data.into_inner();
+    let mut db = db.write();
+    let db_value = db.get(name);
+    // 先检查原数据是否为数组, 再校验数据, 替换集合返回 400 而不是 422
+    if db_value.is_some() {
+        check_db_value_is_array(db_value, name, true)?;
+    }
+    schemas.validate(name, &data_value)?;
+    check_storable(storage, &data_value)?;
+    if db_value.is_none() {
+        print_debug("新建资源", name);
+        events.changed(name, None, Some(&data_value));
+        inset_and_write(&mut db, storage, name, data_value.clone());
+        let location = location(origin, 1, name, &Value::Null);
+        return Ok(Either::Left(
+            status::Created::new(location).body(data_value),
+        ));
+    }
+    events.updated(name, &Value::Null, &data_value);
+    inset_and_write(&mut db, storage, name, data_value.clone());
+    Ok(Either::Right(data_value))
+}
+
+/// 查找 name 属性, 如果不存在返回 Err
+/// 如果存在, 但是数据不是数组, 返回 Err
+/// 如果存在, 且数据是数组, 那么查找 id, 如果不存在返回 Err
+/// 忽略 data 中的 id, 替换原数组中对应 id 的数据, 保持原有位置
+#[rocket::put("/<name>/<id>", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
+fn put_name_id(
+    name: &str,
+    id: &str,
+    data: JsonBody<Value>,
+    db: &Db,
+    ids: &State<Ids>,
+    schemas: &State<Schemas>,
+    storage: &Storage,
+    events: &Events,
+) -> Result<Value, status::Custom<Value>> {
+    let mut db = db.write();
+    let data_value = data.into_inner();
+    replace_item(&mut db, ids, schemas, storage, events, name, id, data_value)
+}
+
+/// 替换数组中对应 id 的数据, 规则见 put_name_id
+#[allow(clippy::too_many_arguments)]
+fn replace_item(
+    db: &mut HashMap<String, Value>,
+    ids: &Ids,
+    schemas: &Schemas,
+    storage: &Storage,
+    events: &Events,
+    name: &str,
+    id: &str,
+    mut data_value: Value,
+) -> Result<Value, status::Custom<Value>> {
+    let db_value = db.get(name);
+    let db_value = check_db_value_is_array(db_value, name, false)?;
+    let db_value = db_value.as_array().unwrap();
+    // 从数组中查找 id
+    let index = db_value
+        .iter()
+        .position(|item| ids.is_value_equal_str(item, id));
+    match index {
+        Some(index) => {
+            // 忽略 data 中的 id, 替换原数组中对应 id 的数据
+            check_data_is_object(&data_value)?;
+            data_value[&ids.field] = ids.get(&db_value[index]).clone();
+            schemas.validate(name, &data_value)?;
+            check_storable(storage, &data_value)?;
+            events.updated(name, ids.get(&data_value), &data_value);
+            let mut db_value: Vec<Value> = db_value.clone();
+            db_value[index] = data_value.clone();
+            inset_and_write(db, storage, name, serde_json::to_value(db_value).unwrap());
+            Ok(data_value)
+        }
+        None => Err(item_not_found(name, id)),
+    }
+}
+
+/// 查找 name 属性, 如果不存在返回 Err
+/// 如果存在, 但是数据是数组, 返回 Err
+/// 如果存在, 且数据不是数组, 按 JSON Merge Patch (RFC 7386) 更新原数据
+/// rank 需要大于 json_patch_name, 否则 json-patch 的请求也会匹配到这里
+#[rocket::patch("/<name>", data = "<data>", rank = 1)]
+fn patch_name(
+    name: &str,
+    data: JsonBody<Value>,
+    db: &Db,
+    schemas: &State<Schemas>,
+    storage: &Storage,
+    events: &Events,
+) -> Result<Value, status::Custom<Value>> {
+    let mut db = db.write();
+    update_value(&mut db, schemas, storage, events, name, data.into_inner())
+}
+
+/// 按 JSON Merge Patch 更新 name 属性, 规则见 patch_name
+fn update_value(
+    db: &mut HashMap<String, Value>,
+    schemas: &Schemas,
+    storage: &Storage,
+    events: &Events,
+    name: &str,
+    data_value: Value,
+) -> Result<Value, status::Custom<Value>> {
+    check_data_is_object(&data_value)?;
+    let db_value = db.get(name);
+    let db_value = check_db_value_is_array(db_value, name, true)?;
+    let mut db_value = db_value.clone();
+    merge_patch(&mut db_value, &data_value);
+    schemas.validate(name, &db_value)?;
+    check_storable(storage, &db_value)?;
+    events.updated(name, &Value::Null, &db_value);
+    inset_and_write(db, storage, name, db_value.clone());
+    Ok(db_value)
+}
+
+/// 查找 name 属性, 如果不存在返回 Err
+/// 如果存在, 但是数据不是数组, 返回 Err
+/// 如果存在, 且数据是数组, 那么查找 id, 如果不存在返回 Err
+/// 忽略 data 中的 id, 按 JSON Merge Patch (RFC 7386) 更新原数组中对应 id 的数据, 保持原有位置
+/// rank 需要大于 json_patch_name_id, 否则 json-patch 的请求也会匹配到这里
+#[rocket::patch("/<name>/<id>", data = "<data>", rank = 1)]
+#[allow(clippy::too_many_arguments)]
+fn patch_name_id(
+    name: &str,
+    id: &str,
+    data: JsonBody<Value>,
+    db: &Db,
+    ids: &State<Ids>,
+    schemas: &State<Schemas>,
+    storage: &Storage,
+    events: &Events,
+) -> Result<Value, status::Custom<Value>> {
+    let mut db = db.write();
+    update_item(
+        &mut db,
+        ids,
+        schemas,
+        storage,
+        events,
+        name,
+        id,
+        data.into_inner(),
+    )
+}
+
+/// 按 JSON Merge Patch 更新数组中对应 id 的数据, 规则见 patch_name_id
+#[allow(clippy::too_many_arguments)]
+fn update_item(
+    db: &mut HashMap<String, Value>,
+    ids: &Ids,
+    schemas: &Schemas,
+    storage: &Storage,
+    events: &Events,
+    name: &str,
+    id: &str,
+    data_value: Value,
+) -> Result<Value, status::Custom<Value>> {
+    let db_value = db.get(name);
+    let db_value = check_db_value_is_array(db_value, name, false)?;
+    let db_value = db_value.as_array().unwrap();
+    // 从数组中查找 id
+    let index = db_value
+        .iter()
+        .position(|item| ids.is_value_equal_str(item, id));
+    match index {
+        Some(index) => {
+            // 忽略 data 中的 id, 更新原数组中对应 id 的数据
+            check_data_is_object(&data_value)?;
+            let mut res_value = db_value[index].clone();
+            merge_patch(&mut res_value, &data_value);
+            res_value[&ids.field] = ids.get(&db_value[index]).clone();
+            schemas.validate(name, &res_value)?;
+            check_storable(storage, &res_value)?;
+            events.updated(name, ids.get(&res_value), &res_value);
+            let mut db_value: Vec<Value> = db_value.clone();
+            db_value[index] = res_value.clone();
+            inset_and_write(db, storage, name, serde_json::to_value(db_value).unwrap());
+            Ok(res_value)
+        }
+        None => Err(item_not_found(name, id)),
+    }
+}
+
+/// 查找 name 属性, 如果不存在返回 Err
+/// 如果存在, 按 JSON Patch (RFC 6902) 更新原数据, 任意操作失败则不做任何修改
+/// 原数据是数组时, 修改后的每个数据都必须是有 id 的对象
+#[rocket::patch("/<name>", format = "application/json-patch+json", data = "<data>")]
+fn json_patch_name(
+    name: &str,
+    data: JsonBody<Patch>,
+    db: &Db,
+    ids: &State<Ids>,
+    schemas: &State<Schemas>,
+    storage: &Storage,
+    events: &Events,
+) -> Result<Value, status::Custom<Value>> {
+    let mut db = db.write();
+    let db_value = check_db_value_is_empty(db.get(name), name)?;
+    let db_value = json_patch(db_value, &data)?;
+    match &db_value {
+        Value::Array(items) => {
+            check_items_have_id(ids, items)?;
+            schemas.validate_items(name, items)?
+        }
+        _ => schemas.validate(name, &db_value)?,
+    }
+    check_storable(storage, &db_value)?;
+    // JSON Patch 可以修改集合中的任意数据, 比较整个资源
+    events.changed(name, db.get(name), Some(&db_value));
+    inset_and_write(&mut db, storage, name, db_value.clone());
+    Ok(db_value)
+}
+
+/// 查找 name 属性, 如果不存在返回 Err
+/// 如果存在, 但是数据不是数组, 返回 Err
+/// 如果存在, 且数据是数组, 那么查找 id, 如果不存在返回 Err
+/// 按 JSON Patch (RFC 6902) 更新原数组中对应 id 的数据, 保持原有位置和 id
+/// 任意操作失败则不做任何修改
+#[rocket::patch(
+    "/<name>/<id>",
+    format = "application/json-patch+json",
+    data = "<data>"
+)]
+#[allow(clippy::too_many_arguments)]
+fn json_patch_name_id(
+    name: &str,
+    id: &str,
+    data: JsonBody<Patch>,
+    db: &Db,
+    ids: &State<Ids>,
+    schemas: &State<Schemas>,
+    storage: &Storage,
+    events: &Events,
+) -> Result<Value, status::Custom<Value>> {
+    let mut db = db.write();
+    let db_value = db.get(name);
+    let db_value = check_db_value_is_array(db_value, name, false)?;
+    let db_value = db_value.as_array().unwrap();
+    // 从数组中查找 id
+    let index = db_value
+        .iter()
+        .position(|item| ids.is_value_equal_str(item, id));
+    match index {
+        Some(index) => {
+            let mut res_value = json_patch(&db_value[index], &data)?;
+            if !res_value.is_object() {
+                return Err(error_with(
+                    Status::UnprocessableEntity,
+                    "Patched item must be a JSON object",
+                    res_value,
+                ));
+            }
+            res_value[&ids.field] = ids.get(&db_value[index]).clone();
+            schemas.validate(name, &res_value)?;
+            check_storable(storage, &res_value)?;
+            events.updated(name, ids.get(&res_value), &res_value);
+            let mut db_value: Vec<Value> = db_value.clone();
+            db_value[index] = res_value.clone();
+            inset_and_write(
+                &mut db,
+                storage,
+                name,
+                serde_json::to_value(db_value).unwrap(),
+            );
+            Ok(res_value)
+        }
+        None => Err(item_not_found(name, id)),
+    }
+}
+
+/// 查找 name 属性, 如果不存在返回 Err
+/// 如果存在, 但是数据不是数组, 返回 Err
+/// 如果存在, 且数据是数组, 那么查找 id, 如果不存在返回 Err
+/// 删除原数组中对应 id 的数据, 返回 204
+#[rocket::delete("/<name>/<id>")]
+fn delete_name_id(
+    name: &str,
+    id: &str,
+    db: &Db,
+    ids: &State<Ids>,
+    storage: &Storage,
+    events: &Events,
+) -> Result<Status, status::Custom<Value>> {
+    let mut db = db.write();
+    remove_item(&mut db, ids, storage, events, name, id)?;
+    Ok(Status::NoContent)
+}
+
+/// 删除数组中对应 id 的数据, 返回删除的数据, 规则见 delete_name_id
+fn remove_item(
+    db: &mut HashMap<String, Value>,
+    ids: &Ids,
+    storage: &Storage,
+    events: &Events,
+    name: &str,
+    id: &str,
+) -> Result<Value, status::Custom<Value>> {
+    let db_value = db.get(name);
+    let db_value = check_db_value_is_array(db_value, name, false)?;
+    let db_value = db_value.as_array().unwrap();
+    // 从数组中查找 id
+    let res_value = db_value
+        .iter()
+        .find(|item| ids.is_value_equal_str(item, id))
+        .cloned();
+    match res_value {
+        Some(res_value) => {
+            // 删除原数组中对应 id 的数据
+            let db_value: Vec<Value> = db_value
+                .iter()
+                .filter(|item| !ids.is_value_equal_str(item, id))
+                .cloned()
+                .collect();
+            events.deleted(name, ids.get(&res_value), &res_value);
+            inset_and_write(db, storage, name, serde_json::to_value(db_value).unwrap());
+            Ok(res_value)
+        }
+        None => Err(item_not_found(name, id)),
+    }
+}
+
+/// 查找 name 属性, 如果不存在返回 Err
+/// 如果存在, 删除整个资源, 返回 204
+#[rocket::delete("/<name>")]
+fn delete_name(
+    name: &str,
+    db: &Db,
+    storage: &Storage,
+    events: &Events,
+) -> Result<Status, status::Custom<Value>> {
+    let mut db = db.write();
+    check_db_value_is_empty(db.get(name), name)?;
+    remove_and_write(&mut db, storage, events, name);
+    Ok(Status::NoContent)
+}
+
+#[derive(Args)]
+pub struct JsonServerArgs {
+    /// Database file path, .json, .json5, .yaml, .yml or .toml, optional with --proxy or --replay
+    #[arg(required_unless_present_any = ["db", "proxy", "replay"], conflicts_with = "db")]
+    path: Option<String>,
+    /// Database file mounted under a prefix, e.g. users.json@/users-api, can be repeated
+    #[arg(long, value_name = "FILE@PREFIX", value_parser = Mount::parse)]
+    db: Vec<Mount>,
+    /// Server host, default: 127.0.0.1
+    #[arg(long)]
+    host: Option<String>,
+    /// Server port, default: 8000
+    #[arg(short, long)]
+    port: Option<u16>,
+    /// Write changes to this file instead of the json file
+    #[arg(short, long, conflicts_with_all = ["read_only", "db"])]
+    out: Option<String>,
+    /// Keep changes in memory only, never write to file
+    #[arg(long)]
+    read_only: bool,
+    /// Wait milliseconds after the last change before writing to file, at most 10 times as long
+    /// while changes keep coming, default: 100
+    #[arg(long)]
+    debounce: Option<u64>,
+    /// Reload the json file when it is changed by others
+    #[arg(short, long)]
+    watch: bool,
+    /// Id field name, default: id
+    #[arg(long)]
+    id: Option<String>,
+    /// How to generate ids for new items, default: increment
+    #[arg(long, value_enum)]
+    id_strategy: Option<IdStrategy>,
+    /// JSON Schema file, maps resource names to schemas for validating writes
+    #[arg(long)]
+    schema: Option<String>,
+    /// Custom routes file, maps routes like /api/* to /$1 before matching
+    #[arg(long)]
+    routes: Option<String>,
+    /// Delay every response by milliseconds
+    #[arg(long)]
+    delay: Option<u64>,
+    /// Add a random delay up to milliseconds
+    #[arg(long)]
+    jitter: Option<u64>,
+    /// Probability between 0 and 1 of responding with --fail-status
+    #[arg(long)]
+    fail_rate: Option<f64>,
+    /// Status code of injected failures, default: 500
+    #[arg(long)]
+    fail_status: Option<u16>,
+    /// Probability between 0 and 1 of dropping the connection
+    #[arg(long)]
+    drop_rate: Option<f64>,
+    /// Per-route delays and failures file, overrides the options above
+    #[arg(long)]
+    faults: Option<String>,
+    /// Directory to write snapshots to, loads the latest snapshots on start
+    #[arg(long)]
+    snapshots: Option<String>,
+    /// Proxy every request to this upstream, e.g. http://localhost:3000
+    #[arg(long)]
+    proxy: Option<String>,
+    /// Record proxied requests and responses into this fixture file
+    #[arg(long, requires = "proxy")]
+    record: Option<String>,
+    /// Replay responses from a fixture file written by --record
+    #[arg(long, conflicts_with = "proxy")]
+    replay: Option<String>,
+    /// Generate fake data from a template file into the database file and exit
+    #[arg(long, requires = "path")]
+    template: Option<String>,
+    /// Seed of the generated fake data, default: 0
+    #[arg(long, requires = "template")]
+    seed: Option<u64>,
+}
+
+impl JsonServerArgs {
+    /// 按模板生成数据并写入到数据文件中
+    fn generate(&self, template: &Path) -> Result<(), Box<dyn Error>> {
+        let template = storage::load(template)?;
+        let id = self.id.as_deref().unwrap_or("id");
+        let db = seed::generate(&template, self.seed.unwrap_or(0), id)?;
+        let out = self
+            .out
+            .as_ref()
+            .or(self.path.as_ref())
+            .ok_or("Missing database file path")?;
+        let out = PathBuf::from(out);
+        storage::write_atomic(&out, &db)?;
+        println!("Generated {}", out.display());
+        Ok(())
+    }
+
+    /// 所有数据文件和挂载的前缀, 前缀不能重叠
+    fn mounts(&self) -> Result<Vec<Mount>, Box<dyn Error>> {
+        // 代理和回放模式可以没有数据文件, 所有请求都由代理处理
+        let mounts = match &self.path {
+            Some(path) => vec![Mount::root(PathBuf::from(path))],
+            None => self.db.clone(),
+        };
+        for (i, mount) in mounts.iter().enumerate() {
+            if let Some(other) = mounts[..i].iter().find(|other| other.overlaps(mount)) {
+                return Err(format!("Prefixes of {} and {} overlap", other, mount).into());
+            }
+        }
+        Ok(mounts)
+    }
+
+    /// 读取一个数据文件, 每个数据文件写回自己的文件, 快照保存在以前缀命名的子目录中
+    fn database(&self, mount: Mount, ids: &Ids) -> Result<Database, Box<dyn Error>> {
+        let data = storage::load(&mount.path)?;
+        let dir = self
+            .snapshots
+            .as_ref()
+            .map(|dir| PathBuf::from(dir).join(mount.base.trim_start_matches('/')));
+        let snapshots = Snapshots::new(data.clone(), dir)?;
+
+        let storage = if self.read_only {
+            Storage::read_only()
+        } else {
+            let out = self.out.as_ref().map_or(mount.path.clone(), PathBuf::from);
+            let debounce = Duration::from_millis(self.debounce.unwrap_or(100));
+            Storage::new(out, debounce)
+        };
+
+        Ok(Database {
+            db: Db::new(data),
+            storage,
+            events: Events::new(ids.clone()),
+            snapshots,
+            mount,
+        })
+    }
+
+    /// 根据参数构建 rocket, 不启动服务, 测试中通过 local client 发送请求
+    fn rocket(&self) -> Result<Rocket<Build>, Box<dyn Error>> {
+        let ids = Ids::new(
+            self.id.clone().unwrap_or("id".to_string()),
+            self.id_strategy.unwrap_or(IdStrategy::Increment),
+        );
+        let databases = self
+            .mounts()?
+            .into_iter()
+            .map(|mount| self.database(mount, &ids))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let schemas = match &self.schema {
+            Some(schema) => Schemas::load(&PathBuf::from(schema))?,
+            None => Schemas::default(),
+        };
+
+        let rewriter = match &self.routes {
+            Some(routes) => Some(Rewriter::load(&PathBuf::from(routes))?),
+            None => None,
+        };
+
+        let fault = Fault {
+            delay: self.delay.unwrap_or(0),
+            jitter: self.jitter.unwrap_or(0),
+            fail_rate: self.fail_rate.unwrap_or(0.0),
+            fail_status: self.fail_status.unwrap_or(500),
+            drop_rate: self.drop_rate.unwrap_or(0.0),
+        };
+        let faults = match &self.faults {
+            Some(faults) => Faults::load(fault, &PathBuf::from(faults))?,
+            None => Faults::new(fault)?,
+        };
+
+        let proxy = match (&self.proxy, &self.replay) {
+            (Some(upstream), _) => Some(Proxy::forward(
+                upstream,
+                self.record.as_ref().map(PathBuf::from),
+            )?),
+            (_, Some(replay)) => Some(Proxy::replay(&PathBuf::from(replay))?),
+            _ => None,
+        };
+
+        let host = self.host.clone().unwrap_or("127.0.0.1".to_string());
+        let config = Config {
+            address: host.parse::<IpAddr>()?,
+            port: self.port.unwrap_or(8000),
+            ..Config::default()
+        };
+
+        let pages = Pages::new()?;
+        let log = RequestLog::default();
+
+        let mut rocket = rocket::build()
+            .configure(config)
+            .manage(ids)
+            .manage(schemas)
+            .manage(pages)
+            .manage(log.clone())
+            .attach(log);
+        if let Some(rewriter) = rewriter {
+            rocket = rocket.attach(rewriter);
+        }
+
+        for database in &databases {
+            let mount = &database.mount;
+            rocket = rocket.attach(database.storage.clone());
+            if self.watch {
+                rocket = rocket.attach(Watcher::new(
+                    mount.path.clone(),
+                    database.db.clone(),
+                    database.storage.clone(),
+                    database.events.clone(),
+                ));
+            }
+            rocket = rocket
+                .mount(
+                    mount.at(""),
+                    routes![
+                        get_db,
+                        get_name,
+                        get_name_id,
+                        get_name_id_nested,
+                        post_name,
+                        post_name_id_nested,
+                        put_name,
+                        put_name_id,
+                        patch_name,
+                        patch_name_id,
+                        json_patch_name,
+                        json_patch_name_id,
+                        delete_name,
+                        delete_name_id,
+                        graphql::graphql,
+                        graphql::graphiql,
+                        openapi::openapi,
+                        ui::dashboard
+                    ],
+                )
+                .mount(mount.at(ADMIN_BASE), admin::routes())
+                .mount(mount.at(EVENTS_PATH), events::routes())
+                .mount(mount.at(DOCS_PATH), openapi::routes())
+                .mount(mount.at(UI_BASE), ui::routes());
+        }
+
+        let mut rocket = rocket
+            .manage(Databases(databases))
+            .mount("/", faults.routes())
+            .register("/", catchers![default_catcher]);
+        if let Some(proxy) = proxy {
+            rocket = rocket.mount("/", proxy.routes());
+        }
+
+        Ok(rocket)
+    }
+}
+
+impl RunCommand for JsonServerArgs {
+    fn run(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(template) = &self.template {
+            return self.generate(&PathBuf::from(template));
+        }
+
+        let rocket = self.rocket()?;
+        let rt = Runtime::new()?;
+
+        rt.block_on(async {
+            rocket.launch().await.unwrap();
+        });
+
+        Ok(())
+    }
+}
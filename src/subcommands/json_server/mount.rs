@@ -0,0 +1,192 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use std::fmt;
+use std::path::PathBuf;
+
+use super::admin::Snapshots;
+use super::db::Db;
+use super::events::Events;
+use super::storage::Storage;
+
+/// 数据文件和挂载的前缀, 如 `--db users.json@/users-api`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mount {
+    pub path: PathBuf,
+    /// 挂载的前缀, 不以 / 结尾, 挂载在根路径时为空字符串
+    pub base: String,
+}
+
+impl Mount {
+    /// 挂载在根路径
+    pub fn root(path: PathBuf) -> Self {
+        Mount {
+            path,
+            base: String::new(),
+        }
+    }
+
+    /// 解析 `FILE@PREFIX`, 前缀以 / 开头, 不能包含空的或以 __ 开头的路径段
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let invalid = || format!("Expected FILE@/prefix, got {}", value);
+        let (path, base) = value.rsplit_once('@').ok_or_else(invalid)?;
+        if path.is_empty() || !base.starts_with('/') {
+            return Err(invalid());
+        }
+        let base = base.trim_end_matches('/');
+        if base
+            .split('/')
+            .skip(1)
+            .any(|segment| segment.is_empty() || segment.starts_with("__"))
+        {
+            return Err(format!("Invalid prefix {}", base));
+        }
+        Ok(Mount {
+            path: PathBuf::from(path),
+            base: base.to_string(),
+        })
+    }
+
+    /// 路由挂载的位置, 如 `/users-api/__admin`
+    pub fn at(&self, path: &str) -> String {
+        match format!("{}{}", self.base, path) {
+            at if at.is_empty() => String::from("/"),
+            at => at,
+        }
+    }
+
+    /// 请求路径是否在前缀下, 按路径段匹配, `/shop` 不包含 `/shopping`
+    pub fn contains(&self, path: &str) -> bool {
+        path.strip_prefix(&self.base)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    /// 去掉前缀后的路径, 不在前缀下时返回 None
+    pub fn strip<'p>(&self, path: &'p str) -> Option<&'p str> {
+        match self.contains(path) {
+            true => Some(&path[self.base.len()..]),
+            false => None,
+        }
+    }
+
+    /// 两个前缀是否有重叠, 重叠时无法判断请求属于哪个数据文件
+    pub fn overlaps(&self, other: &Mount) -> bool {
+        self.contains(&other.base) || other.contains(&self.base)
+    }
+}
+
+impl fmt::Display for Mount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.path.display(), self.at(""))
+    }
+}
+
+/// 一个数据文件的数据, 写入目标, 事件和快照
+pub struct Database {
+    pub mount: Mount,
+    pub db: Db,
+    pub storage: Storage,
+    pub events: Events,
+    pub snapshots: Snapshots,
+}
+
+/// 所有数据文件, 请求按路径的前缀找到对应的数据文件
+pub struct Databases(pub Vec<Database>);
+
+impl Databases {
+    pub fn find(&self, path: &str) -> Option<&Database> {
+        self.0.iter().find(|database| database.mount.contains(path))
+    }
+
+    /// 去掉前缀后的请求路径, 不在任何前缀下时返回 None
+    pub fn strip<'p>(&self, path: &'p str) -> Option<&'p str> {
+        self.find(path)
+            .and_then(|database| database.mount.strip(path))
+    }
+}
+
+/// 按请求路径找到数据文件, handler 中使用 `&Db`, `&Storage` 等参数获取对应的数据
+fn database<'r>(req: &'r Request<'_>) -> Outcome<&'r Database, ()> {
+    let database = req
+        .rocket()
+        .state::<Databases>()
+        .and_then(|databases| databases.find(req.uri().path().as_str()));
+    match database {
+        Some(database) => Outcome::Success(database),
+        None => Outcome::Forward(Status::NotFound),
+    }
+}
+
+macro_rules! database_guard {
+    ($type:ty, $field:ident) => {
+        #[rocket::async_trait]
+        impl<'r> FromRequest<'r> for &'r $type {
+            type Error = ();
+
+            async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
+                database(req).map(|database| &database.$field)
+            }
+        }
+    };
+}
+
+database_guard!(Db, db);
+database_guard!(Storage, storage);
+database_guard!(Events, events);
+database_guard!(Snapshots, snapshots);
+
+/// 当前数据文件的前缀, 用于生成链接, 根路径为空字符串
+pub struct Base<'r>(pub &'r str);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Base<'r> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
+        database(req).map(|database| Base(&database.mount.base))
+    }
+}
+
+impl fmt::Display for Base<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mounts() {
+        let mount = Mount::parse("data/users.json@/users-api/").unwrap();
+        assert_eq!(mount.path, PathBuf::from("data/users.json"));
+        assert_eq!(mount.base, "/users-api");
+        assert_eq!(mount.at("/__admin"), "/users-api/__admin");
+        assert_eq!(Mount::parse("a@b.json@/v1/shop").unwrap().base, "/v1/shop");
+        assert_eq!(Mount::parse("db.json@/").unwrap().at(""), "/");
+        for value in [
+            "db.json",
+            "@/shop",
+            "db.json@shop",
+            "db.json@/__ui",
+            "db.json@//a",
+        ] {
+            assert!(Mount::parse(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn match_paths() {
+        let shop = Mount::parse("shop.json@/shop").unwrap();
+        assert!(shop.contains("/shop"));
+        assert_eq!(shop.strip("/shop/posts/1"), Some("/posts/1"));
+        assert_eq!(shop.strip("/shopping"), None);
+
+        let root = Mount::root(PathBuf::from("db.json"));
+        assert_eq!(root.strip("/posts"), Some("/posts"));
+        assert!(root.overlaps(&shop));
+        assert!(shop.overlaps(&Mount::parse("v2.json@/shop/v2").unwrap()));
+        assert!(!shop.overlaps(&Mount::parse("users.json@/users").unwrap()));
+    }
+}
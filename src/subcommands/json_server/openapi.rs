@@ -0,0 +1,598 @@
+use rocket::response::content::RawHtml;
+use rocket::serde::json::serde_json::{json, Map};
+use rocket::serde::json::Value;
+use rocket::State;
+use std::collections::{BTreeSet, HashMap};
+
+use super::graphql::{type_name, Names};
+use super::id::Ids;
+use super::mount::Base;
+use super::relations::foreign_key;
+use super::Db;
+
+/// 文档页面的路径
+pub const DOCS_PATH: &str = "/__docs";
+
+/// 集合查询支持的参数, 与 query_items 和 populate 一致
+const LIST_PARAMS: [(&str, &str, &str); 9] = [
+    ("_sort", "string", "Fields to sort by, comma separated"),
+    ("_order", "string", "asc or desc for each sort field"),
+    ("_page", "integer", "Page number, starting at 1"),
+    ("_limit", "integer", "Items per page, or items after _start"),
+    ("_start", "integer", "Index of the first item"),
+    ("_end", "integer", "Index after the last item"),
+    ("q", "string", "Full-text search in string fields"),
+    ("_embed", "string", "Child resources to include"),
+    ("_expand", "string", "Parent resources to include"),
+];
+
+/// 根据所有的值推断 JSON Schema
+/// 对象合并所有出现过的字段, 每个值都有的字段为 required, 类型不一致时不限制类型
+fn infer_schema(values: &[&Value]) -> Value {
+    let nullable = values.iter().any(|value| value.is_null());
+    let values: Vec<&Value> = values.iter().copied().filter(|v| !v.is_null()).collect();
+    let types: BTreeSet<&str> = values.iter().map(|value| json_type(value)).collect();
+    let ty = match types.len() {
+        1 => types.into_iter().next().unwrap(),
+        2 if types.contains("integer") && types.contains("number") => "number",
+        _ => {
+            return if nullable {
+                json!({ "nullable": true })
+            } else {
+                json!({})
+            }
+        }
+    };
+
+    let mut schema = json!({ "type": ty });
+    if ty == "object" {
+        let mut fields: Vec<&String> = values
+            .iter()
+            .filter_map(|value| value.as_object())
+            .flat_map(|object| object.keys())
+            .collect();
+        fields.sort();
+        fields.dedup();
+        let mut properties = Map::new();
+        let mut required = vec![];
+        for field in fields {
+            let present: Vec<&Value> = values.iter().filter_map(|value| value.get(field)).collect();
+            if present.len() == values.len() {
+                required.push(json!(field));
+            }
+            properties.insert(field.clone(), infer_schema(&present));
+        }
+        schema["properties"] = Value::Object(properties);
+        if !required.is_empty() {
+            schema["required"] = Value::Array(required);
+        }
+    } else if ty == "array" {
+        let items: Vec<&Value> = values
+            .iter()
+            .filter_map(|value| value.as_array())
+            .flatten()
+            .collect();
+        schema["items"] = infer_schema(&items);
+    }
+    if nullable {
+        schema["nullable"] = json!(true);
+    }
+    schema
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+/// JSON 内容
+fn content(schema: Value) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+/// 返回 JSON 的响应
+fn ok(description: &str, schema: Value) -> Value {
+    json!({ "description": description, "content": content(schema) })
+}
+
+fn error_response(description: &str) -> Value {
+    ok(description, schema_ref("Error"))
+}
+
+fn path_param(name: &str) -> Value {
+    json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } })
+}
+
+fn query_param(name: &str, ty: &str, description: &str) -> Value {
+    json!({ "name": name, "in": "query", "schema": { "type": ty }, "description": description })
+}
+
+/// 集合的查询参数, 字段名可以按相等过滤
+fn list_params(item: &Value) -> Vec<Value> {
+    let mut params: Vec<Value> = LIST_PARAMS
+        .iter()
+        .map(|(name, ty, description)| query_param(name, ty, description))
+        .collect();
+    if let Some(Value::Object(properties)) = item.get("properties") {
+        for field in properties.keys().filter(|field| !field.starts_with('_')) {
+            let description = format!(
+                "Filter by {0}, also {0}_ne, {0}_gte, {0}_lte and {0}_like",
+                field
+            );
+            params.push(query_param(field, "string", &description));
+        }
+    }
+    params
+}
+
+/// PATCH 的请求体, 支持 JSON Merge Patch 和 JSON Patch
+fn patch_body() -> Value {
+    json!({
+        "required": true,
+        "content": {
+            "application/json": { "schema": { "type": "object" } },
+            "application/json-patch+json": { "schema": { "type": "array", "items": { "type": "object" } } }
+        }
+    })
+}
+
+/// 集合的路径, 包括 `/<name>`, `/<name>/{id}`, 以及存在外键关联时的 `/<name>/{id}/<child>`
+fn collection_paths(
+    paths: &mut Map<String, Value>,
+    name: &str,
+    ty: &str,
+    schema: &Value,
+    children: &[(&String, &str)],
+) {
+    let item = schema_ref(ty);
+    let list = json!({ "type": "array", "items": item });
+    let body = json!({ "required": true, "content": content(item.clone()) });
+    paths.insert(
+        format!("/{}", name),
+        json!({
+            "get": {
+                "summary": format!("List {}", name),
+                "tags": [name],
+                "parameters": list_params(schema),
+                "responses": {
+                    "200": {
+                        "description": format!("Filtered, sorted and paginated {}", name),
+                        "headers": {
+                            "X-Total-Count": { "schema": { "type": "integer" } },
+                            "Link": { "schema": { "type": "string" } }
+                        },
+                        "content": content(list.clone())
+                    },
+                    "400": error_response("Invalid query parameter")
+                }
+            },
+            "post": {
+                "summary": format!("Create an item in {}", name),
+                "tags": [name],
+                "requestBody": body,
+                "responses": {
+                    "201": ok("Created item", item.clone()),
+                    "409": error_response("Duplicate id"),
+                    "422": error_response("Invalid item")
+                }
+            },
+            "delete": {
+                "summary": format!("Delete all {}", name),
+                "tags": [name],
+                "responses": { "204": { "description": "Deleted" } }
+            }
+        }),
+    );
+    paths.insert(
+        format!("/{}/{{id}}", name),
+        json!({
+            "parameters": [path_param("id")],
+            "get": {
+                "summary": format!("Get an item of {}", name),
+                "tags": [name],
+                "parameters": [
+                    query_param("_embed", "string", "Child resources to include"),
+                    query_param("_expand", "string", "Parent resources to include")
+                ],
+                "responses": {
+                    "200": ok("Item", item.clone()),
+                    "404": error_response("Item not found")
+                }
+            },
+            "put": {
+                "summary": format!("Replace an item of {}", name),
+                "tags": [name],
+                "requestBody": body,
+                "responses": {
+                    "200": ok("Replaced item", item.clone()),
+                    "404": error_response("Item not found"),
+                    "422": error_response("Invalid item")
+                }
+            },
+            "patch": {
+                "summary": format!("Update an item of {}", name),
+                "tags": [name],
+                "requestBody": patch_body(),
+                "responses": {
+                    "200": ok("Updated item", item.clone()),
+                    "404": error_response("Item not found"),
+                    "422": error_response("Invalid patch")
+                }
+            },
+            "delete": {
+                "summary": format!("Delete an item of {}", name),
+                "tags": [name],
+                "responses": {
+                    "204": { "description": "Deleted" },
+                    "404": error_response("Item not found")
+                }
+            }
+        }),
+    );
+    for (child, child_ty) in children {
+        let child_item = schema_ref(child_ty);
+        paths.insert(
+            format!("/{}/{{id}}/{}", name, child),
+            json!({
+                "parameters": [path_param("id")],
+                "get": {
+                    "summary": format!("List {} of an item of {}", child, name),
+                    "tags": [name],
+                    "responses": {
+                        "200": ok(child, json!({ "type": "array", "items": child_item })),
+                        "404": error_response("Item not found")
+                    }
+                },
+                "post": {
+                    "summary": format!("Create an item in {} with {}", child, foreign_key(name)),
+                    "tags": [name],
+                    "requestBody": { "required": true, "content": content(child_item.clone()) },
+                    "responses": {
+                        "201": ok("Created item", child_item),
+                        "404": error_response("Item not found"),
+                        "422": error_response("Invalid item")
+                    }
+                }
+            }),
+        );
+    }
+}
+
+/// 非集合资源的路径, 只有 `/<name>`
+fn value_paths(paths: &mut Map<String, Value>, name: &str, schema: Value) {
+    let body = json!({ "required": true, "content": content(schema.clone()) });
+    paths.insert(
+        format!("/{}", name),
+        json!({
+            "get": {
+                "summary": format!("Get {}", name),
+                "tags": [name],
+                "responses": { "200": ok(name, schema.clone()) }
+            },
+            "put": {
+                "summary": format!("Replace {}", name),
+                "tags": [name],
+                "requestBody": body,
+                "responses": {
+                    "200": ok(name, schema.clone()),
+                    "422": error_response("Invalid value")
+                }
+            },
+            "patch": {
+                "summary": format!("Update {}", name),
+                "tags": [name],
+                "requestBody": patch_body(),
+                "responses": {
+                    "200": ok(name, schema),
+                    "422": error_response("Invalid patch")
+                }
+            },
+            "delete": {
+                "summary": format!("Delete {}", name),
+                "tags": [name],
+                "responses": { "204": { "description": "Deleted" } }
+            }
+        }),
+    );
+}
+
+/// 根据当前的数据生成 OpenAPI 3 文档
+/// 集合的数据结构由所有数据推断, 对象和其他数据作为整体推断
+/// 挂载在前缀下时, 前缀作为 servers 的地址
+fn document(db: &HashMap<String, Value>, ids: &Ids, base: &str) -> Value {
+    let mut names: Vec<&String> = db.keys().collect();
+    names.sort();
+    let collections: Vec<&String> = names
+        .iter()
+        .copied()
+        .filter(|name| db[*name].is_array())
+        .collect();
+
+    // 不同的资源可能得到相同的类型名, 如 post 和 posts, 重复时加上数字后缀
+    let mut unique = Names::reserved(&["Error"]);
+    let components: HashMap<&String, String> = names
+        .iter()
+        .map(|name| (*name, unique.unique(type_name(name))))
+        .collect();
+
+    let mut paths = Map::new();
+    let mut schemas = Map::new();
+    for name in &names {
+        let ty = components[*name].clone();
+        match &db[*name] {
+            Value::Array(items) => {
+                let items: Vec<&Value> = items.iter().collect();
+                let mut schema = infer_schema(&items);
+                if schema["type"] != "object" {
+                    schema = json!({ "type": "object", "properties": {} });
+                }
+                if schema["properties"].get(&ids.field).is_none() {
+                    schema["properties"][&ids.field] = json!({});
+                }
+                // 没有 id 时会自动生成
+                if let Some(Value::Array(required)) = schema.get_mut("required") {
+                    required.retain(|field| *field != ids.field);
+                }
+                let key = foreign_key(name);
+                let children: Vec<(&String, &str)> = collections
+                    .iter()
+                    .filter(|child| {
+                        db[**child]
+                            .as_array()
+                            .is_some_and(|items| items.iter().any(|item| item.get(&key).is_some()))
+                    })
+                    .map(|child| (*child, components[*child].as_str()))
+                    .collect();
+                collection_paths(&mut paths, name, &ty, &schema, &children);
+                schemas.insert(ty, schema);
+            }
+            value => {
+                let schema = infer_schema(&[value]);
+                value_paths(&mut paths, name, schema_ref(&ty));
+                schemas.insert(ty, schema);
+            }
+        }
+    }
+    paths.insert(
+        String::from("/db"),
+        json!({
+            "get": {
+                "summary": "Get the whole database",
+                "responses": { "200": ok("Database", json!({ "type": "object" })) }
+            }
+        }),
+    );
+    paths.insert(
+        String::from("/graphql"),
+        json!({
+            "post": {
+                "summary": "Run a GraphQL query or mutation",
+                "requestBody": {
+                    "required": true,
+                    "content": content(json!({
+                        "type": "object",
+                        "required": ["query"],
+                        "properties": {
+                            "query": { "type": "string" },
+                            "variables": { "type": "object" },
+                            "operationName": { "type": "string" }
+                        }
+                    }))
+                },
+                "responses": { "200": ok("GraphQL response", json!({ "type": "object" })) }
+            }
+        }),
+    );
+    schemas.insert(
+        String::from("Error"),
+        json!({
+            "type": "object",
+            "required": ["status", "error", "message"],
+            "properties": {
+                "status": { "type": "integer" },
+                "error": { "type": "string" },
+                "message": { "type": "string" },
+                "details": {}
+            }
+        }),
+    );
+
+    let mut doc = json!({
+        "openapi": "3.0.3",
+        "info": { "title": "json-server", "version": env!("CARGO_PKG_VERSION") },
+        "paths": paths,
+        "components": { "schemas": schemas }
+    });
+    if !base.is_empty() {
+        doc["servers"] = json!([{ "url": base }]);
+    }
+    doc
+}
+
+/// 转义 HTML 中的特殊字符
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// 文档页面, 列出所有路径和数据结构
+fn docs_html(doc: &Value) -> String {
+    let mut html = String::from(concat!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>json-server</title>\n",
+        "<style>body{font-family:sans-serif;margin:2em}td{padding:2px 12px}",
+        "code{background:#f4f4f4}pre{background:#f4f4f4;padding:8px}</style>\n",
+        "</head>\n<body>\n<h1>json-server</h1>\n",
+    ));
+    let url = format!(
+        "{}/openapi.json",
+        escape(doc["servers"][0]["url"].as_str().unwrap_or_default())
+    );
+    html.push_str(&format!(
+        "<p>OpenAPI document: <a href=\"{}\">{}</a></p>\n<h2>Paths</h2>\n<table>\n",
+        url, url
+    ));
+    if let Some(paths) = doc["paths"].as_object() {
+        for (path, item) in paths {
+            for (method, operation) in item.as_object().into_iter().flatten() {
+                if method == "parameters" {
+                    continue;
+                }
+                html.push_str(&format!(
+                    "<tr><td><b>{}</b></td><td><code>{}</code></td><td>{}</td></tr>\n",
+                    method.to_uppercase(),
+                    escape(path),
+                    escape(operation["summary"].as_str().unwrap_or_default())
+                ));
+            }
+        }
+    }
+    html.push_str("</table>\n<h2>Schemas</h2>\n");
+    if let Some(schemas) = doc["components"]["schemas"].as_object() {
+        for (name, schema) in schemas {
+            let schema = rocket::serde::json::serde_json::to_string_pretty(schema).unwrap();
+            html.push_str(&format!(
+                "<h3>{}</h3>\n<pre>{}</pre>\n",
+                escape(name),
+                escape(&schema)
+            ));
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// OpenAPI 文档, 按请求时的数据生成
+#[rocket::get("/openapi.json")]
+pub fn openapi(db: &Db, ids: &State<Ids>, base: Base) -> Value {
+    let db = db.read();
+    document(&db, ids, base.0)
+}
+
+/// 文档页面
+#[rocket::get("/")]
+fn docs(db: &Db, ids: &State<Ids>, base: Base) -> RawHtml<String> {
+    let db = db.read();
+    RawHtml(docs_html(&document(&db, ids, base.0)))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![docs]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::serde::json::serde_json;
+
+    #[test]
+    fn infer_schemas() {
+        let items = [
+            json!({ "id": 1, "title": "a", "views": 1, "tags": ["x"] }),
+            json!({ "id": 2, "title": null, "views": 1.5 }),
+        ];
+        let items: Vec<&Value> = items.iter().collect();
+        assert_eq!(
+            infer_schema(&items),
+            json!({
+                "type": "object",
+                "properties": {
+                    "id": { "type": "integer" },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "title": { "type": "string", "nullable": true },
+                    "views": { "type": "number" }
+                },
+                "required": ["id", "title", "views"]
+            })
+        );
+        assert_eq!(infer_schema(&[&json!(1), &json!("a")]), json!({}));
+        assert_eq!(infer_schema(&[]), json!({}));
+    }
+
+    #[test]
+    fn document_paths() {
+        let db: HashMap<String, Value> = serde_json::from_value(json!({
+            "posts": [{ "id": 1, "title": "a" }],
+            "comments": [{ "id": 1, "postId": 1 }],
+            "profile": { "name": "typicode" }
+        }))
+        .unwrap();
+        let doc = document(&db, &Ids::default(), "");
+        let paths = doc["paths"].as_object().unwrap();
+        let mut keys: Vec<&str> = paths.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "/comments",
+                "/comments/{id}",
+                "/db",
+                "/graphql",
+                "/posts",
+                "/posts/{id}",
+                "/posts/{id}/comments",
+                "/profile"
+            ]
+        );
+        assert_eq!(
+            doc["components"]["schemas"]["Post"]["required"],
+            json!(["title"])
+        );
+        let params = &doc["paths"]["/posts"]["get"]["parameters"];
+        assert!(params
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|p| p["name"] == "title"));
+        assert!(docs_html(&doc).contains("<code>/posts/{id}/comments</code>"));
+
+        assert!(doc.get("servers").is_none());
+        let doc = document(&db, &Ids::default(), "/shop");
+        assert_eq!(doc["servers"], json!([{ "url": "/shop" }]));
+        assert!(docs_html(&doc).contains(r#"<a href="/shop/openapi.json">"#));
+    }
+
+    #[test]
+    fn unique_components() {
+        let db: HashMap<String, Value> = serde_json::from_value(json!({
+            "post": { "title": "a" },
+            "posts": [{ "id": 1, "postId": 1 }],
+            "_": { "name": "b" },
+            "errors": [{ "id": 1 }]
+        }))
+        .unwrap();
+        let doc = document(&db, &Ids::default(), "");
+        let schemas = doc["components"]["schemas"].as_object().unwrap();
+        let mut keys: Vec<&str> = schemas.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["Error", "Error2", "Post", "Post2", "Resource"]);
+        let schema = |path: &str, method: &str, status: &str| {
+            doc["paths"][path][method]["responses"][status]["content"]["application/json"]["schema"]
+                .clone()
+        };
+        assert_eq!(
+            schema("/post", "get", "200"),
+            json!({ "$ref": "#/components/schemas/Post" })
+        );
+        assert_eq!(
+            schema("/posts/{id}", "get", "200"),
+            json!({ "$ref": "#/components/schemas/Post2" })
+        );
+        assert_eq!(
+            schema("/posts/{id}/posts", "post", "201"),
+            json!({ "$ref": "#/components/schemas/Post2" })
+        );
+    }
+}
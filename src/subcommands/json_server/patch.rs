@@ -0,0 +1,144 @@
+use json_patch::{Patch, PatchErrorKind};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::{serde_json::json, Value};
+
+use super::error::error_with;
+
+/// JSON Merge Patch (RFC 7386)
+/// patch 是对象时递归合并, 值为 null 的字段会被删除
+/// patch 不是对象时直接替换 target
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => {
+            *target = patch.clone();
+            return;
+        }
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// JSON Patch (RFC 6902)
+/// 在副本上依次执行 add/remove/replace/move/copy/test 操作, 全部成功才返回结果
+/// test 失败返回 409, 路径不存在等其他错误返回 422
+pub fn json_patch(target: &Value, patch: &Patch) -> Result<Value, status::Custom<Value>> {
+    let mut value = target.clone();
+    json_patch::patch(&mut value, patch).map_err(|e| {
+        let status = match e.kind {
+            PatchErrorKind::TestFailed => Status::Conflict,
+            _ => Status::UnprocessableEntity,
+        };
+        error_with(
+            status,
+            format!("Patch failed: {}", e),
+            json!({ "operation": e.operation }),
+        )
+    })?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::serde::json::serde_json::{self, json};
+
+    #[test]
+    fn merge_patch_rfc_examples() {
+        let cases = [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (
+                json!({"a": "b"}),
+                json!({"b": "c"}),
+                json!({"a": "b", "b": "c"}),
+            ),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (
+                json!({"a": "b", "b": "c"}),
+                json!({"a": null}),
+                json!({"b": "c"}),
+            ),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "c"}), json!({"a": ["b"]}), json!({"a": ["b"]})),
+            (
+                json!({"a": {"b": "c"}}),
+                json!({"a": {"b": "d", "c": null}}),
+                json!({"a": {"b": "d"}}),
+            ),
+            (
+                json!({"a": [{"b": "c"}]}),
+                json!({"a": [1]}),
+                json!({"a": [1]}),
+            ),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "b"}), json!(["c"]), json!(["c"])),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (
+                json!({"e": null}),
+                json!({"a": 1}),
+                json!({"e": null, "a": 1}),
+            ),
+            (
+                json!([1, 2]),
+                json!({"a": "b", "c": null}),
+                json!({"a": "b"}),
+            ),
+            (
+                json!({}),
+                json!({"a": {"bb": {"ccc": null}}}),
+                json!({"a": {"bb": {}}}),
+            ),
+        ];
+        for (mut target, patch, expected) in cases {
+            merge_patch(&mut target, &patch);
+            assert_eq!(target, expected);
+        }
+    }
+
+    #[test]
+    fn json_patch_operations() {
+        let target = json!({ "id": 1, "title": "json-server", "tags": ["a"] });
+        let patch: Patch = serde_json::from_value(json!([
+            { "op": "test", "path": "/title", "value": "json-server" },
+            { "op": "replace", "path": "/title", "value": "rust" },
+            { "op": "add", "path": "/tags/-", "value": "b" },
+            { "op": "copy", "from": "/title", "path": "/name" },
+            { "op": "move", "from": "/name", "path": "/author" },
+            { "op": "remove", "path": "/tags/0" }
+        ]))
+        .unwrap();
+        assert_eq!(
+            json_patch(&target, &patch).unwrap(),
+            json!({ "id": 1, "title": "rust", "tags": ["b"], "author": "rust" })
+        );
+    }
+
+    #[test]
+    fn json_patch_errors() {
+        let target = json!({ "title": "json-server" });
+        let patch: Patch = serde_json::from_value(json!([
+            { "op": "replace", "path": "/title", "value": "rust" },
+            { "op": "test", "path": "/title", "value": "json-server" }
+        ]))
+        .unwrap();
+        assert_eq!(json_patch(&target, &patch).unwrap_err().0, Status::Conflict);
+
+        let patch: Patch =
+            serde_json::from_value(json!([{ "op": "remove", "path": "/author" }])).unwrap();
+        assert_eq!(
+            json_patch(&target, &patch).unwrap_err().0,
+            Status::UnprocessableEntity
+        );
+    }
+}
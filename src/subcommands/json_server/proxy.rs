@@ -0,0 +1,427 @@
+use base64::{engine::general_purpose, Engine as _};
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Uri};
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::{Header, Method, Status};
+use rocket::response::{self, Responder, Response};
+use rocket::route::{self, Handler, Route};
+use rocket::serde::json::{serde_json, Value};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::task;
+use rocket::Request;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::error::error;
+use super::mount::Databases;
+
+/// 低于故障注入, 先执行延迟和故障注入, 高于其他所有路由
+const PROXY_RANK: isize = -50;
+
+/// 请求体的大小上限
+const BODY_LIMIT: u64 = 10 * 1024 * 1024;
+
+/// 不转发的请求头, 响应不做压缩, 以便记录为文本
+const SKIP_REQUEST_HEADERS: [&str; 4] = ["host", "connection", "content-length", "accept-encoding"];
+
+/// 不转发也不记录的响应头, 由 Rocket 重新生成
+const SKIP_RESPONSE_HEADERS: [&str; 5] = [
+    "connection",
+    "date",
+    "content-length",
+    "transfer-encoding",
+    "keep-alive",
+];
+
+/// 记录的请求, 按方法, 路径, 查询参数和请求体匹配
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RecordedRequest {
+    method: String,
+    path: String,
+    #[serde(default)]
+    query: String,
+    #[serde(default)]
+    body: Value,
+    /// 请求体不是 UTF-8 文本, body 为 base64 编码
+    #[serde(default, skip_serializing_if = "is_false")]
+    base64: bool,
+}
+
+impl RecordedRequest {
+    fn new(req: &Request, body: &[u8]) -> Self {
+        let uri = req.uri();
+        let (body, base64) = parse_body(body);
+        RecordedRequest {
+            method: req.method().as_str().to_string(),
+            path: uri.path().as_str().to_string(),
+            query: uri
+                .query()
+                .map(|q| q.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            body,
+            base64,
+        }
+    }
+
+    /// 查询参数的顺序不影响匹配, JSON 请求体按值比较
+    fn matches(&self, other: &RecordedRequest) -> bool {
+        let query = |query: &str| {
+            let mut pairs: Vec<&str> = query.split('&').filter(|s| !s.is_empty()).collect();
+            pairs.sort_unstable();
+            pairs.join("&")
+        };
+        self.method.eq_ignore_ascii_case(&other.method)
+            && self.path == other.path
+            && query(&self.query) == query(&other.query)
+            && self.body == other.body
+            && self.base64 == other.base64
+    }
+}
+
+/// 记录的响应, 同名的响应头用 `, ` 连接
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RecordedResponse {
+    status: u16,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    body: Value,
+    /// 响应体不是 UTF-8 文本, body 为 base64 编码
+    #[serde(default, skip_serializing_if = "is_false")]
+    base64: bool,
+}
+
+impl RecordedResponse {
+    fn is_json(&self) -> bool {
+        self.headers
+            .get("content-type")
+            .is_some_and(|content_type| content_type.contains("json"))
+    }
+}
+
+impl<'r> Responder<'r, 'static> for RecordedResponse {
+    fn respond_to(self, _req: &'r Request<'_>) -> response::Result<'static> {
+        let body = match &self.body {
+            Value::Null => vec![],
+            Value::String(text) if self.base64 => general_purpose::STANDARD
+                .decode(text)
+                .map_err(|_| Status::InternalServerError)?,
+            Value::String(text) if !self.is_json() => text.clone().into_bytes(),
+            body => serde_json::to_vec(body).unwrap(),
+        };
+        let mut response = Response::build();
+        response
+            .status(Status::new(self.status))
+            .sized_body(body.len(), Cursor::new(body));
+        for (name, value) in self.headers {
+            response.header(Header::new(name, value));
+        }
+        response.ok()
+    }
+}
+
+/// 一次请求和响应
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Exchange {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+/// 请求体和响应体能解析为 JSON 时保存为 JSON, 否则保存为文本, 为空时为 null
+/// 不是 UTF-8 的内容保存为 base64 编码的文本, 此时第二个值为 true
+fn parse_body(body: &[u8]) -> (Value, bool) {
+    if body.is_empty() {
+        return (Value::Null, false);
+    }
+    if let Ok(value) = serde_json::from_slice(body) {
+        return (value, false);
+    }
+    match std::str::from_utf8(body) {
+        Ok(text) => (Value::String(text.to_string()), false),
+        Err(_) => (Value::String(general_purpose::STANDARD.encode(body)), true),
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// 记录文件中的所有请求和响应
+struct Fixtures {
+    /// 每条记录是否已经回放过
+    exchanges: Mutex<Vec<(Exchange, bool)>>,
+    /// 记录模式下写入的文件
+    record: Option<PathBuf>,
+    /// 保证同一时间只有一个写入, 写入时读取最新的记录
+    io_lock: Mutex<()>,
+}
+
+impl Fixtures {
+    fn load(path: &Path) -> Result<Vec<Exchange>, Box<dyn Error>> {
+        let data = fs::read_to_string(path)?;
+        serde_json::from_str(&data)
+            .map_err(|e| format!("Invalid fixture file {}: {}", path.display(), e).into())
+    }
+
+    fn new(exchanges: Vec<Exchange>, record: Option<PathBuf>) -> Self {
+        let exchanges = exchanges.into_iter().map(|e| (e, false)).collect();
+        Fixtures {
+            exchanges: Mutex::new(exchanges),
+            record,
+            io_lock: Mutex::new(()),
+        }
+    }
+
+    /// 按顺序回放第一条没有回放过的匹配记录, 都回放过后重复最后一条
+    /// 同一个请求多次记录的不同响应, 回放时保持原来的顺序
+    fn replay(&self, request: &RecordedRequest) -> Option<RecordedResponse> {
+        let mut exchanges = self.exchanges.lock().unwrap();
+        let mut matched = exchanges
+            .iter_mut()
+            .filter(|(exchange, _)| exchange.request.matches(request))
+            .peekable();
+        let mut last = None;
+        while let Some((exchange, replayed)) = matched.next() {
+            if !*replayed || matched.peek().is_none() {
+                *replayed = true;
+                last = Some(exchange.response.clone());
+                break;
+            }
+        }
+        last
+    }
+
+    /// 追加一条记录, 在阻塞线程中写入文件, 不占用异步线程
+    async fn record(self: &Arc<Self>, exchange: Exchange) {
+        if self.record.is_none() {
+            return;
+        }
+        self.exchanges.lock().unwrap().push((exchange, true));
+        let fixtures = self.clone();
+        let _ = task::spawn_blocking(move || fixtures.write()).await;
+    }
+
+    /// 写入所有记录, 先写入临时文件再重命名
+    /// 序列化在 io_lock 中进行, 后写入的总是最新的记录
+    fn write(&self) {
+        let path = match &self.record {
+            Some(path) => path,
+            None => return,
+        };
+        let _guard = self.io_lock.lock().unwrap();
+        let content = {
+            let exchanges = self.exchanges.lock().unwrap();
+            let list: Vec<&Exchange> = exchanges.iter().map(|(exchange, _)| exchange).collect();
+            serde_json::to_string_pretty(&list).unwrap()
+        };
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+        if let Err(e) = fs::write(&tmp_path, content).and_then(|_| fs::rename(&tmp_path, path)) {
+            println!("Unable to write fixture file {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// 代理模式, 所有请求转发到上游, 可以同时记录请求和响应到文件
+/// 回放模式, 不访问上游, 从记录文件中查找匹配的响应
+/// 管理接口 `/__admin` 和 `/__events` 不转发
+#[derive(Clone)]
+pub struct Proxy {
+    upstream: Option<(String, Client<HttpConnector>)>,
+    fixtures: Arc<Fixtures>,
+}
+
+impl Proxy {
+    /// 转发到上游, 指定 record 时记录到文件, 文件已存在时追加到原有记录之后
+    pub fn forward(upstream: &str, record: Option<PathBuf>) -> Result<Self, Box<dyn Error>> {
+        let uri: Uri = upstream.parse()?;
+        if uri.scheme_str() != Some("http") || uri.host().is_none() {
+            return Err(format!("Invalid upstream {}, only http:// is supported", upstream).into());
+        }
+        let exchanges = match &record {
+            Some(path) if path.exists() => Fixtures::load(path)?,
+            _ => vec![],
+        };
+        Ok(Proxy {
+            upstream: Some((upstream.trim_end_matches('/').to_string(), Client::new())),
+            fixtures: Arc::new(Fixtures::new(exchanges, record)),
+        })
+    }
+
+    /// 从记录文件中回放
+    pub fn replay(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(Proxy {
+            upstream: None,
+            fixtures: Arc::new(Fixtures::new(Fixtures::load(path)?, None)),
+        })
+    }
+
+    /// 所有方法的路由
+    pub fn routes(&self) -> Vec<Route> {
+        [
+            Method::Get,
+            Method::Post,
+            Method::Put,
+            Method::Patch,
+            Method::Delete,
+            Method::Head,
+            Method::Options,
+        ]
+        .into_iter()
+        .map(|method| Route::ranked(PROXY_RANK, method, "/<_..>", self.clone()))
+        .collect()
+    }
+
+    /// 转发请求, 返回上游的响应
+    async fn send(
+        &self,
+        upstream: &str,
+        client: &Client<HttpConnector>,
+        req: &Request<'_>,
+        body: Vec<u8>,
+    ) -> Result<RecordedResponse, Box<dyn Error>> {
+        let mut builder = hyper::Request::builder()
+            .method(req.method().as_str())
+            .uri(format!("{}{}", upstream, req.uri()));
+        for header in req.headers().iter() {
+            if !SKIP_REQUEST_HEADERS.contains(&header.name().as_str().to_lowercase().as_str()) {
+                builder = builder.header(header.name().as_str(), header.value());
+            }
+        }
+        let response = client.request(builder.body(Body::from(body))?).await?;
+
+        let mut headers: BTreeMap<String, String> = BTreeMap::new();
+        for (name, value) in response.headers() {
+            if SKIP_RESPONSE_HEADERS.contains(&name.as_str()) {
+                continue;
+            }
+            let value = String::from_utf8_lossy(value.as_bytes());
+            headers
+                .entry(name.to_string())
+                .and_modify(|joined| {
+                    joined.push_str(", ");
+                    joined.push_str(&value);
+                })
+                .or_insert_with(|| value.into_owned());
+        }
+        let status = response.status().as_u16();
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let (body, base64) = parse_body(&body);
+        Ok(RecordedResponse {
+            status,
+            headers,
+            body,
+            base64,
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl Handler for Proxy {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let path = req.uri().path().as_str();
+        let databases = req.rocket().state::<Databases>();
+        if databases
+            .and_then(|databases| databases.strip(path))
+            .unwrap_or(path)
+            .starts_with("/__")
+        {
+            return route::Outcome::forward(data, Status::NotFound);
+        }
+        let body = match data.open(BODY_LIMIT.bytes()).into_bytes().await {
+            Ok(body) if body.is_complete() => body.into_inner(),
+            Ok(_) => {
+                let message = format!("Request body is larger than {} bytes", BODY_LIMIT);
+                return route::Outcome::from(req, error(Status::PayloadTooLarge, message));
+            }
+            Err(e) => return route::Outcome::from(req, error(Status::BadRequest, e.to_string())),
+        };
+        let request = RecordedRequest::new(req, &body);
+
+        let (upstream, client) = match &self.upstream {
+            Some(upstream) => upstream,
+            None => {
+                return match self.fixtures.replay(&request) {
+                    Some(response) => route::Outcome::from(req, response),
+                    None => {
+                        let message =
+                            format!("No recorded response for {} {}", req.method(), req.uri());
+                        route::Outcome::from(req, error(Status::NotFound, message))
+                    }
+                };
+            }
+        };
+        let response = match self.send(upstream, client, req, body).await {
+            Ok(response) => response,
+            Err(e) => {
+                let message = format!("Upstream request failed: {}", e);
+                return route::Outcome::from(req, error(Status::BadGateway, message));
+            }
+        };
+        self.fixtures
+            .record(Exchange {
+                request,
+                response: response.clone(),
+            })
+            .await;
+        route::Outcome::from(req, response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::serde::json::serde_json::json;
+
+    fn exchange(method: &str, path: &str, query: &str, body: Value, status: u16) -> Exchange {
+        serde_json::from_value(json!({
+            "request": { "method": method, "path": path, "query": query, "body": body },
+            "response": { "status": status, "headers": { "content-type": "application/json" } }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn match_requests() {
+        let recorded = exchange("POST", "/posts", "a=1&b=2", json!({ "x": 1, "y": 2 }), 201);
+        let request = exchange("post", "/posts", "b=2&a=1", json!({ "y": 2, "x": 1 }), 0).request;
+        assert!(recorded.request.matches(&request));
+        let request = exchange("POST", "/posts", "a=1", json!({ "x": 1, "y": 2 }), 0).request;
+        assert!(!recorded.request.matches(&request));
+        let request = exchange("POST", "/posts", "a=1&b=2", json!("text"), 0).request;
+        assert!(!recorded.request.matches(&request));
+
+        assert_eq!(parse_body(b""), (Value::Null, false));
+        assert_eq!(parse_body(b"{\"a\": 1}"), (json!({ "a": 1 }), false));
+        assert_eq!(parse_body(b"<html>"), (json!("<html>"), false));
+        assert_eq!(parse_body(&[0xff, 0x00]), (json!("/wA="), true));
+    }
+
+    #[test]
+    fn replay_in_order() {
+        let fixtures = Fixtures::new(
+            vec![
+                exchange("GET", "/posts", "", Value::Null, 200),
+                exchange("DELETE", "/posts/1", "", Value::Null, 204),
+                exchange("GET", "/posts", "", Value::Null, 404),
+            ],
+            None,
+        );
+        let get = exchange("GET", "/posts", "", Value::Null, 0).request;
+        let status = |fixtures: &Fixtures| fixtures.replay(&get).map(|r| r.status);
+        assert_eq!(status(&fixtures), Some(200));
+        assert_eq!(status(&fixtures), Some(404));
+        assert_eq!(status(&fixtures), Some(404));
+        let missing = exchange("GET", "/comments", "", Value::Null, 0).request;
+        assert_eq!(fixtures.replay(&missing), None);
+    }
+}
@@ -0,0 +1,581 @@
+use regex::RegexBuilder;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, status, Responder};
+use rocket::serde::json::{serde_json::json, Value};
+use std::cmp::Ordering;
+
+use super::error::error_with;
+use super::id::Ids;
+
+/// 分页时默认每页数量
+const DEFAULT_PAGE_LIMIT: usize = 10;
+
+/// 过滤参数的操作符后缀, 如 `views_gte`
+const FILTER_SUFFIXES: [&str; 4] = ["_ne", "_gte", "_lte", "_like"];
+
+/// 请求中的查询参数, 按原始顺序保存, 同名参数可以出现多次
+pub struct QueryParams {
+    /// 请求路径, 用于生成分页的 Link
+    path: String,
+    /// 解码后的 (key, value)
+    pairs: Vec<(String, String)>,
+    /// id 字段名称, 以 `_` 开头时也可以用于过滤
+    id_field: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for QueryParams {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let uri = req.uri();
+        let pairs = match uri.query() {
+            Some(query) => query
+                .segments()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            None => vec![],
+        };
+        let id_field = match req.rocket().state::<Ids>() {
+            Some(ids) => ids.field.clone(),
+            None => String::from("id"),
+        };
+        Outcome::Success(QueryParams {
+            path: uri.path().to_string(),
+            pairs,
+            id_field,
+        })
+    }
+}
+
+impl QueryParams {
+    /// 由请求路径和已经解码的参数构建, 用于 GraphQL 参数和测试
+    pub fn from_pairs(path: &str, pairs: &[(&str, &str)]) -> Self {
+        QueryParams {
+            path: path.to_string(),
+            pairs: pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            id_field: String::from("id"),
+        }
+    }
+
+    /// 获取指定参数的第一个值
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// 获取指定参数的所有值, 同时支持 `a=1&a=2` 和 `a=1,2` 两种形式
+    pub fn get_all<'s>(&'s self, key: &'s str) -> impl Iterator<Item = &'s str> {
+        self.pairs
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .flat_map(|(_, v)| v.split(','))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    }
+
+    /// 获取指定参数并解析为数字
+    fn get_usize(&self, key: &str) -> Result<Option<usize>, status::Custom<Value>> {
+        match self.get(key) {
+            Some(value) => value.parse::<usize>().map(Some).map_err(|_| {
+                bad_request(
+                    format!("Query parameter {} must be a non-negative integer", key),
+                    value,
+                )
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// 用于过滤的参数, 以 `_` 开头的和 `q` 为保留参数
+    /// id 字段除外, 如 `--id _id` 时可以用 `_id` 和 `_id_ne` 过滤
+    fn filters(&self) -> impl Iterator<Item = &(String, String)> {
+        self.pairs
+            .iter()
+            .filter(|(k, _)| self.is_id_filter(k) || (!k.starts_with('_') && k != "q"))
+    }
+
+    /// 参数是否为 id 字段的过滤条件
+    fn is_id_filter(&self, key: &str) -> bool {
+        key.strip_prefix(self.id_field.as_str())
+            .is_some_and(|rest| rest.is_empty() || FILTER_SUFFIXES.contains(&rest))
+    }
+
+    /// 替换 `_page` 后生成新的链接
+    fn page_link(&self, page: usize) -> String {
+        let mut query: Vec<String> = self
+            .pairs
+            .iter()
+            .filter(|(k, _)| k != "_page")
+            .map(|(k, v)| format!("{}={}", encode(k), encode(v)))
+            .collect();
+        query.push(format!("_page={}", page));
+        format!("{}?{}", self.path, query.join("&"))
+    }
+}
+
+/// 对查询参数进行百分号编码
+fn encode(s: &str) -> String {
+    let mut encoded = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b',' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// 400, details 中带上错误的参数值
+fn bad_request(message: String, value: &str) -> status::Custom<Value> {
+    error_with(Status::BadRequest, message, json!({ "value": value }))
+}
+
+/// 列表响应, 分页时会带上 X-Total-Count 和 Link 响应头
+pub struct Collection {
+    pub body: Value,
+    pub total: Option<usize>,
+    pub link: Option<String>,
+}
+
+impl<'r> Responder<'r, 'static> for Collection {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let mut res = self.body.respond_to(req)?;
+        let mut expose = vec![];
+        if let Some(total) = self.total {
+            res.set_raw_header("X-Total-Count", total.to_string());
+            expose.push("X-Total-Count");
+        }
+        if let Some(link) = self.link {
+            res.set_raw_header("Link", link);
+            expose.push("Link");
+        }
+        if !expose.is_empty() {
+            res.set_raw_header("Access-Control-Expose-Headers", expose.join(", "));
+        }
+        Ok(res)
+    }
+}
+
+/// 获取字段值, 支持 `a.b.c` 形式的嵌套字段
+pub fn get_field<'v>(item: &'v Value, field: &str) -> Option<&'v Value> {
+    field.split('.').try_fold(item, |value, key| match value {
+        Value::Object(map) => map.get(key),
+        Value::Array(arr) => key.parse::<usize>().ok().and_then(|i| arr.get(i)),
+        _ => None,
+    })
+}
+
+/// 将 Value 转换为字符串用于比较, 字符串不带引号
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/// 比较字段值与查询值, 两者都是数字时按数字比较, 否则按字符串比较
+fn compare_with_query(value: &Value, query: &str) -> Ordering {
+    if let (Some(a), Ok(b)) = (value.as_f64(), query.parse::<f64>()) {
+        return a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+    }
+    value_to_string(value).as_str().cmp(query)
+}
+
+/// 比较两个 Value 用于排序
+/// 不同类型按 null < bool < number < string < array < object 排列
+fn compare_values(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    fn rank(value: Option<&Value>) -> u8 {
+        match value {
+            None | Some(Value::Null) => 0,
+            Some(Value::Bool(_)) => 1,
+            Some(Value::Number(_)) => 2,
+            Some(Value::String(_)) => 3,
+            Some(Value::Array(_)) => 4,
+            Some(Value::Object(_)) => 5,
+        }
+    }
+    match (a, b) {
+        (Some(Value::Bool(a)), Some(Value::Bool(b))) => a.cmp(b),
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Some(Value::String(a)), Some(Value::String(b))) => a.cmp(b),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// 单个过滤条件
+enum Filter {
+    Eq(String),
+    Ne(String),
+    Gte(String),
+    Lte(String),
+    Like(regex::Regex),
+}
+
+impl Filter {
+    fn matches(&self, value: Option<&Value>) -> bool {
+        match (self, value) {
+            (Filter::Ne(query), Some(value)) => value_to_string(value) != *query,
+            (Filter::Ne(_), None) => true,
+            (_, None) => false,
+            (Filter::Eq(query), Some(value)) => value_to_string(value) == *query,
+            (Filter::Gte(query), Some(value)) => compare_with_query(value, query).is_ge(),
+            (Filter::Lte(query), Some(value)) => compare_with_query(value, query).is_le(),
+            (Filter::Like(re), Some(value)) => re.is_match(&value_to_string(value)),
+        }
+    }
+}
+
+/// 解析过滤参数, 返回 (字段, 条件)
+fn parse_filter(key: &str, value: &str) -> Result<(String, Filter), status::Custom<Value>> {
+    for suffix in FILTER_SUFFIXES {
+        if let Some(field) = key.strip_suffix(suffix) {
+            let filter = match suffix {
+                "_ne" => Filter::Ne(value.to_string()),
+                "_gte" => Filter::Gte(value.to_string()),
+                "_lte" => Filter::Lte(value.to_string()),
+                _ => {
+                    let re = RegexBuilder::new(value)
+                        .case_insensitive(true)
+                        .build()
+                        .map_err(|e| {
+                            bad_request(format!("Invalid regex for {}: {}", key, e), value)
+                        })?;
+                    Filter::Like(re)
+                }
+            };
+            return Ok((field.to_string(), filter));
+        }
+    }
+    Ok((key.to_string(), Filter::Eq(value.to_string())))
+}
+
+/// 过滤数组, 相同参数之间为或关系, 不同参数之间为与关系
+fn filter_items(
+    items: Vec<Value>,
+    params: &QueryParams,
+) -> Result<Vec<Value>, status::Custom<Value>> {
+    // 按参数名分组, 保持出现顺序
+    let mut groups: Vec<(&str, Vec<(String, Filter)>)> = vec![];
+    for (key, value) in params.filters() {
+        let filter = parse_filter(key, value)?;
+        match groups.iter_mut().find(|(k, _)| k == key) {
+            Some((_, filters)) => filters.push(filter),
+            None => groups.push((key.as_str(), vec![filter])),
+        }
+    }
+    if groups.is_empty() {
+        return Ok(items);
+    }
+    Ok(items
+        .into_iter()
+        .filter(|item| {
+            groups.iter().all(|(_, filters)| {
+                filters
+                    .iter()
+                    .any(|(field, filter)| filter.matches(get_field(item, field)))
+            })
+        })
+        .collect())
+}
+
+/// 判断 Value 中是否有字符串包含关键字, 会递归查找嵌套的对象和数组
+/// keyword 需要是小写
+fn contains_keyword(value: &Value, keyword: &str) -> bool {
+    match value {
+        Value::String(s) => s.to_lowercase().contains(keyword),
+        Value::Array(arr) => arr.iter().any(|item| contains_keyword(item, keyword)),
+        Value::Object(map) => map.values().any(|item| contains_keyword(item, keyword)),
+        _ => false,
+    }
+}
+
+/// 按 `q` 进行全文搜索, 忽略大小写
+fn search_items(items: Vec<Value>, params: &QueryParams) -> Vec<Value> {
+    let keyword = match params.get("q") {
+        Some(q) if !q.is_empty() => q.to_lowercase(),
+        _ => return items,
+    };
+    items
+        .into_iter()
+        .filter(|item| contains_keyword(item, &keyword))
+        .collect()
+}
+
+/// 按 `_sort` 和 `_order` 排序, 支持多个字段, 用逗号分隔
+fn sort_items(items: &mut [Value], params: &QueryParams) -> Result<(), status::Custom<Value>> {
+    let sort = match params.get("_sort") {
+        Some(sort) => sort,
+        None => return Ok(()),
+    };
+    let fields: Vec<&str> = sort.split(',').map(str::trim).collect();
+    let orders: Vec<&str> = params
+        .get("_order")
+        .map(|order| order.split(',').map(str::trim).collect())
+        .unwrap_or_default();
+    let mut keys = vec![];
+    for (i, field) in fields.into_iter().enumerate() {
+        let desc = match orders.get(i).copied().unwrap_or("asc") {
+            "asc" | "ASC" => false,
+            "desc" | "DESC" => true,
+            order => {
+                return Err(bad_request(
+                    String::from("Query parameter _order must be asc or desc"),
+                    order,
+                ))
+            }
+        };
+        keys.push((field, desc));
+    }
+    // sort_by 是稳定排序, 相等的元素保持原有顺序
+    items.sort_by(|a, b| {
+        keys.iter()
+            .map(|(field, desc)| {
+                let ordering = compare_values(get_field(a, field), get_field(b, field));
+                if *desc {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    Ok(())
+}
+
+/// 对数组进行搜索, 过滤, 排序和分页
+/// `q` 搜索所有字符串字段
+/// `_page` 和 `_limit` 按页分页, 返回 X-Total-Count 和 Link
+/// `_start`, `_end` 和 `_limit` 按下标截取, 返回 X-Total-Count
+pub fn query_items(
+    items: Vec<Value>,
+    params: &QueryParams,
+) -> Result<Collection, status::Custom<Value>> {
+    let items = search_items(items, params);
+    let mut items = filter_items(items, params)?;
+    sort_items(&mut items, params)?;
+    let total = items.len();
+
+    let page = params.get_usize("_page")?;
+    let limit = params.get_usize("_limit")?;
+    let start = params.get_usize("_start")?;
+    let end = params.get_usize("_end")?;
+
+    if let Some(page) = page {
+        let page = page.max(1);
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let last = if limit == 0 {
+            1
+        } else {
+            total.div_ceil(limit).max(1)
+        };
+        let items: Vec<Value> = items
+            .into_iter()
+            .skip((page - 1).saturating_mul(limit))
+            .take(limit)
+            .collect();
+
+        let mut links = vec![format!("<{}>; rel=\"first\"", params.page_link(1))];
+        if page > 1 {
+            let prev = (page - 1).min(last);
+            links.push(format!("<{}>; rel=\"prev\"", params.page_link(prev)));
+        }
+        if page < last {
+            links.push(format!("<{}>; rel=\"next\"", params.page_link(page + 1)));
+        }
+        links.push(format!("<{}>; rel=\"last\"", params.page_link(last)));
+
+        return Ok(Collection {
+            body: Value::Array(items),
+            total: Some(total),
+            link: Some(links.join(", ")),
+        });
+    }
+
+    if start.is_some() || end.is_some() || limit.is_some() {
+        let start = start.unwrap_or(0);
+        let end = match (end, limit) {
+            (Some(end), _) => end,
+            (None, Some(limit)) => start.saturating_add(limit),
+            (None, None) => total,
+        };
+        let items: Vec<Value> = items
+            .into_iter()
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect();
+        return Ok(Collection {
+            body: Value::Array(items),
+            total: Some(total),
+            link: None,
+        });
+    }
+
+    Ok(Collection {
+        body: Value::Array(items),
+        total: None,
+        link: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn posts() -> Vec<Value> {
+        vec![
+            json!({ "id": 1, "title": "json-server", "views": 10, "author": { "name": "typicode" } }),
+            json!({ "id": 2, "title": "rocket", "views": 30, "author": { "name": "sergio" } }),
+            json!({ "id": "3", "title": "Rust tools", "views": 20, "author": { "name": "typicode" } }),
+        ]
+    }
+
+    fn ids(collection: &Collection) -> Vec<String> {
+        collection
+            .body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| value_to_string(&item["id"]))
+            .collect()
+    }
+
+    #[test]
+    fn filter_by_field() {
+        let res = query_items(
+            posts(),
+            &QueryParams::from_pairs("/posts", &[("author.name", "typicode")]),
+        )
+        .unwrap();
+        assert_eq!(ids(&res), vec!["1", "3"]);
+        assert_eq!(res.total, None);
+
+        let res = query_items(
+            posts(),
+            &QueryParams::from_pairs("/posts", &[("id", "1"), ("id", "3")]),
+        )
+        .unwrap();
+        assert_eq!(ids(&res), vec!["1", "3"]);
+    }
+
+    #[test]
+    fn filter_by_operators() {
+        let res = query_items(
+            posts(),
+            &QueryParams::from_pairs("/posts", &[("views_gte", "20")]),
+        )
+        .unwrap();
+        assert_eq!(ids(&res), vec!["2", "3"]);
+
+        let res = query_items(
+            posts(),
+            &QueryParams::from_pairs("/posts", &[("views_lte", "20"), ("id_ne", "1")]),
+        )
+        .unwrap();
+        assert_eq!(ids(&res), vec!["3"]);
+
+        let res = query_items(
+            posts(),
+            &QueryParams::from_pairs("/posts", &[("title_like", "^RUST")]),
+        )
+        .unwrap();
+        assert_eq!(ids(&res), vec!["3"]);
+
+        assert!(query_items(
+            posts(),
+            &QueryParams::from_pairs("/posts", &[("title_like", "(")])
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn full_text_search() {
+        let res = query_items(
+            posts(),
+            &QueryParams::from_pairs("/posts", &[("q", "TYPICODE")]),
+        )
+        .unwrap();
+        assert_eq!(ids(&res), vec!["1", "3"]);
+
+        let res = query_items(
+            posts(),
+            &QueryParams::from_pairs("/posts", &[("q", "rust"), ("views_gte", "20")]),
+        )
+        .unwrap();
+        assert_eq!(ids(&res), vec!["3"]);
+
+        // 只搜索字符串字段
+        let res = query_items(posts(), &QueryParams::from_pairs("/posts", &[("q", "30")])).unwrap();
+        assert!(ids(&res).is_empty());
+    }
+
+    #[test]
+    fn sort_by_fields() {
+        let res = query_items(
+            posts(),
+            &QueryParams::from_pairs("/posts", &[("_sort", "views"), ("_order", "desc")]),
+        )
+        .unwrap();
+        assert_eq!(ids(&res), vec!["2", "3", "1"]);
+
+        let res = query_items(
+            posts(),
+            &QueryParams::from_pairs(
+                "/posts",
+                &[("_sort", "author.name,views"), ("_order", "asc,desc")],
+            ),
+        )
+        .unwrap();
+        assert_eq!(ids(&res), vec!["2", "3", "1"]);
+    }
+
+    #[test]
+    fn paginate() {
+        let res = query_items(
+            posts(),
+            &QueryParams::from_pairs("/posts", &[("_page", "2"), ("_limit", "2")]),
+        )
+        .unwrap();
+        assert_eq!(ids(&res), vec!["3"]);
+        assert_eq!(res.total, Some(3));
+        assert_eq!(
+            res.link.unwrap(),
+            "</posts?_limit=2&_page=1>; rel=\"first\", \
+            </posts?_limit=2&_page=1>; rel=\"prev\", \
+            </posts?_limit=2&_page=2>; rel=\"last\""
+        );
+
+        let res = query_items(
+            posts(),
+            &QueryParams::from_pairs("/posts", &[("_start", "1"), ("_end", "2")]),
+        )
+        .unwrap();
+        assert_eq!(ids(&res), vec!["2"]);
+        assert_eq!(res.total, Some(3));
+
+        let res = query_items(
+            posts(),
+            &QueryParams::from_pairs("/posts", &[("_start", "1"), ("_limit", "5")]),
+        )
+        .unwrap();
+        assert_eq!(ids(&res), vec!["2", "3"]);
+
+        assert!(query_items(
+            posts(),
+            &QueryParams::from_pairs("/posts", &[("_page", "x")])
+        )
+        .is_err());
+    }
+}
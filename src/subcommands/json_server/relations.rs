@@ -0,0 +1,144 @@
+use rocket::serde::json::Value;
+use std::collections::HashMap;
+
+use super::id::{is_id_equal_str, Ids};
+use super::query::QueryParams;
+
+/// 单数形式, 如 posts -> post, categories -> category
+pub fn singular(name: &str) -> String {
+    if let Some(stem) = name.strip_suffix("ies") {
+        format!("{}y", stem)
+    } else if let Some(stem) = name.strip_suffix('s') {
+        stem.to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// 复数形式, 如 post -> posts, category -> categories
+pub fn plural(name: &str) -> String {
+    match name.strip_suffix('y') {
+        Some(stem) if !stem.ends_with(['a', 'e', 'i', 'o', 'u']) => format!("{}ies", stem),
+        _ => format!("{}s", name),
+    }
+}
+
+/// 外键名称, 如 posts -> postId
+pub fn foreign_key(name: &str) -> String {
+    format!("{}Id", singular(name))
+}
+
+/// 获取 id 或外键的字符串形式, 用于比较
+fn id_to_string(id_value: &Value) -> Option<String> {
+    match id_value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// 查找子资源中外键等于 id 的数据
+pub fn children<'v>(
+    db: &'v HashMap<String, Value>,
+    name: &str,
+    child: &str,
+    id: &str,
+) -> impl Iterator<Item = &'v Value> {
+    let key = foreign_key(name);
+    let id = id.to_string();
+    db.get(child)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(move |item| is_id_equal_str(&item[&key], &id))
+}
+
+/// 查找外键对应的父资源, `_expand=post` 会在 posts 中查找 postId 对应的数据
+fn parent<'v>(
+    db: &'v HashMap<String, Value>,
+    ids: &Ids,
+    item: &Value,
+    parent: &str,
+) -> Option<&'v Value> {
+    let id = id_to_string(&item[format!("{}Id", parent)])?;
+    [plural(parent), parent.to_string()]
+        .iter()
+        .find_map(|name| db.get(name).and_then(Value::as_array))?
+        .iter()
+        .find(|item| is_id_equal_str(ids.get(item), &id))
+}
+
+/// 按 `_embed` 和 `_expand` 参数填充关联数据
+/// `_embed=comments` 把 comments 中 postId 等于当前 id 的数据放入 comments 字段
+/// `_expand=post` 把 posts 中 id 等于当前 postId 的数据放入 post 字段
+pub fn populate(
+    db: &HashMap<String, Value>,
+    ids: &Ids,
+    name: &str,
+    item: &mut Value,
+    params: &QueryParams,
+) {
+    if !item.is_object() {
+        return;
+    }
+    if let Some(id) = id_to_string(ids.get(item)) {
+        for child in params.get_all("_embed") {
+            let values: Vec<Value> = children(db, name, child, &id).cloned().collect();
+            item[child] = Value::Array(values);
+        }
+    }
+    for name in params.get_all("_expand") {
+        if let Some(value) = parent(db, ids, item, name) {
+            item[name] = value.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::serde::json::serde_json::{self, json};
+
+    #[test]
+    fn inflect() {
+        assert_eq!(singular("posts"), "post");
+        assert_eq!(singular("categories"), "category");
+        assert_eq!(plural("post"), "posts");
+        assert_eq!(plural("category"), "categories");
+        assert_eq!(plural("day"), "days");
+        assert_eq!(foreign_key("posts"), "postId");
+    }
+
+    #[test]
+    fn embed_and_expand() {
+        let db: HashMap<String, Value> = serde_json::from_value(json!({
+            "posts": [{ "id": 1, "title": "json-server" }],
+            "comments": [
+                { "id": 1, "body": "some comment", "postId": 1 },
+                { "id": 2, "body": "other comment", "postId": "1" },
+                { "id": 3, "body": "another post", "postId": 2 }
+            ]
+        }))
+        .unwrap();
+
+        let mut post = db["posts"][0].clone();
+        populate(
+            &db,
+            &Ids::default(),
+            "posts",
+            &mut post,
+            &QueryParams::from_pairs("/posts", &[("_embed", "comments")]),
+        );
+        assert_eq!(post["comments"].as_array().unwrap().len(), 2);
+
+        let mut comment = db["comments"][0].clone();
+        populate(
+            &db,
+            &Ids::default(),
+            "comments",
+            &mut comment,
+            &QueryParams::from_pairs("/comments", &[("_expand", "post")]),
+        );
+        assert_eq!(comment["post"]["title"], "json-server");
+    }
+}
@@ -0,0 +1,232 @@
+use regex::{Captures, Regex};
+use rocket::data::Data;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::serde::de::{Deserialize, DeserializeOwned, Deserializer, MapAccess, Visitor};
+use rocket::serde::json::serde_json;
+use rocket::Request;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use crate::tools::print_debug;
+
+/// 一条重写规则, 如 `/blog/:resource/:id/show` => `/:resource/:id`
+struct Rule {
+    /// 由规则左边生成的正则, `:name` 匹配一段路径, `*` 匹配任意内容
+    pattern: Regex,
+    /// 规则左边是否包含查询参数, 包含时匹配 `path?query`
+    with_query: bool,
+    /// 规则右边, `:name` 已转换为 `${name}`, `$1` 已转换为 `${_1}`, 可以直接用于 Captures::expand
+    target: String,
+}
+
+/// 自定义路由, 在匹配路由前按规则重写请求的 uri
+/// 按文件中的顺序匹配, 只使用第一条匹配的规则
+pub struct Rewriter(Vec<Rule>);
+
+impl Rewriter {
+    /// 从文件中读取规则, 如
+    /// {"/api/*": "/$1", "/blog/:resource/:id/show": "/:resource/:id"}
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Rewriter::new(load_rules(path)?)
+    }
+
+    fn new(rules: Vec<(String, String)>) -> Result<Self, Box<dyn Error>> {
+        let rules = rules
+            .into_iter()
+            .map(|(from, to)| Rule::new(&from, &to))
+            .collect::<Result<_, _>>()?;
+        Ok(Rewriter(rules))
+    }
+
+    /// 重写 uri, 没有匹配的规则时返回 None
+    /// 原请求的查询参数会追加到重写后的 uri 中
+    fn rewrite(&self, path: &str, query: Option<&str>) -> Option<String> {
+        let full = match query {
+            Some(query) => format!("{}?{}", path, query),
+            None => path.to_string(),
+        };
+        self.0.iter().find_map(|rule| {
+            let input = if rule.with_query { &full } else { path };
+            let captures = rule.pattern.captures(input)?;
+            let mut uri = String::new();
+            captures.expand(&rule.target, &mut uri);
+            match query {
+                Some(query) if !rule.with_query => {
+                    uri.push(if uri.contains('?') { '&' } else { '?' });
+                    uri.push_str(query);
+                }
+                _ => {}
+            }
+            Some(uri)
+        })
+    }
+}
+
+impl Rule {
+    fn new(from: &str, to: &str) -> Result<Self, Box<dyn Error>> {
+        let target = Regex::new(r":(\w+)")
+            .unwrap()
+            .replace_all(to, |captures: &Captures| format!("${{{}}}", &captures[1]));
+        // `$1` 只对应 `*`, 不受 `:name` 的分组序号影响
+        let target = Regex::new(r"\$(?:(\d+)|\{(\d+)\})")
+            .unwrap()
+            .replace_all(&target, |captures: &Captures| {
+                let index = captures.get(1).or(captures.get(2)).unwrap().as_str();
+                format!("${{{}}}", wildcard_group(index))
+            })
+            .into_owned();
+        Ok(Rule {
+            pattern: route_pattern(from)?,
+            with_query: from.contains('?'),
+            target,
+        })
+    }
+}
+
+/// 第几个 `*` 对应的分组名, 如 `_1`
+fn wildcard_group(index: impl fmt::Display) -> String {
+    format!("_{}", index)
+}
+
+/// 由路由生成正则, `:name` 匹配一段路径, `*` 匹配任意内容
+/// `*` 按出现的顺序命名为 `_1`, `_2`..., 不与 `:name` 共用序号
+pub fn route_pattern(route: &str) -> Result<Regex, Box<dyn Error>> {
+    let token = Regex::new(r":(\w+)|\*").unwrap();
+    let mut pattern = String::from("^");
+    let mut last = 0;
+    let mut wildcards = 0;
+    for captures in token.captures_iter(route) {
+        let matched = captures.get(0).unwrap();
+        pattern.push_str(&regex::escape(&route[last..matched.start()]));
+        match captures.get(1) {
+            Some(name) => pattern.push_str(&format!("(?P<{}>[^/?&]+)", name.as_str())),
+            None => {
+                wildcards += 1;
+                pattern.push_str(&format!("(?P<{}>.*)", wildcard_group(wildcards)));
+            }
+        }
+        last = matched.end();
+    }
+    pattern.push_str(&regex::escape(&route[last..]));
+    pattern.push('$');
+    Ok(Regex::new(&pattern).map_err(|e| format!("Invalid route {}: {}", route, e))?)
+}
+
+/// 按文件中的顺序读取路由和对应的配置
+pub fn load_rules<V: DeserializeOwned>(path: &Path) -> Result<Vec<(String, V)>, Box<dyn Error>> {
+    let data = fs::read_to_string(path)?;
+    Ok(serde_json::Deserializer::from_str(&data).deserialize_map(OrderedRules(PhantomData))?)
+}
+
+/// 按文件中的顺序读取规则, serde_json 的 Map 会按 key 排序
+struct OrderedRules<V>(PhantomData<V>);
+
+impl<'de, V: Deserialize<'de>> Visitor<'de> for OrderedRules<V> {
+    type Value = Vec<(String, V)>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of routes")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut rules = vec![];
+        while let Some(rule) = map.next_entry()? {
+            rules.push(rule);
+        }
+        Ok(rules)
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Rewriter {
+    fn info(&self) -> Info {
+        Info {
+            name: "json-server routes",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        let uri = req.uri();
+        let rewritten = self.rewrite(uri.path().as_str(), uri.query().map(|q| q.as_str()));
+        if let Some(rewritten) = rewritten {
+            match Origin::parse_owned(rewritten) {
+                Ok(origin) => {
+                    print_debug("重写路由", &origin);
+                    req.set_uri(origin);
+                }
+                Err(e) => println!("Unable to rewrite {}: {}", req.uri(), e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewriter(rules: &[(&str, &str)]) -> Rewriter {
+        Rewriter::new(
+            rules
+                .iter()
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn rewrite_routes() {
+        let rewriter = rewriter(&[
+            ("/api/v1/*", "/$1"),
+            ("/blog/:resource/:id/show", "/:resource/:id"),
+            ("/posts/:category", "/posts?category=:category"),
+            ("/articles?id=:id", "/posts/:id"),
+            ("/api/:version/*/*", "/$2?v=:version&from=${1}"),
+        ]);
+        assert_eq!(
+            rewriter.rewrite("/api/v1/posts/1", None).unwrap(),
+            "/posts/1"
+        );
+        assert_eq!(
+            rewriter.rewrite("/api/v1/posts", Some("_page=2")).unwrap(),
+            "/posts?_page=2"
+        );
+        assert_eq!(
+            rewriter.rewrite("/blog/posts/1/show", None).unwrap(),
+            "/posts/1"
+        );
+        assert_eq!(
+            rewriter.rewrite("/posts/rust", Some("_sort=id")).unwrap(),
+            "/posts?category=rust&_sort=id"
+        );
+        assert_eq!(
+            rewriter.rewrite("/articles", Some("id=3")).unwrap(),
+            "/posts/3"
+        );
+        assert_eq!(
+            rewriter.rewrite("/api/v2/old/posts", None).unwrap(),
+            "/posts?v=v2&from=old"
+        );
+        assert_eq!(rewriter.rewrite("/posts", None), None);
+        assert_eq!(rewriter.rewrite("/blog/posts/1", None), None);
+    }
+
+    #[test]
+    fn keep_file_order() {
+        let rules = serde_json::Deserializer::from_str(r#"{"/b": "/1", "/a": "/2"}"#)
+            .deserialize_map(OrderedRules(PhantomData))
+            .unwrap();
+        assert_eq!(
+            rules,
+            vec![
+                (String::from("/b"), String::from("/1")),
+                (String::from("/a"), String::from("/2"))
+            ]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use jsonschema::JSONSchema;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::{serde_json::json, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::error::error_with;
+use super::storage;
+
+/// 每个资源对应的 JSON Schema
+/// 集合资源的 schema 描述其中的每一项, 其他资源的 schema 描述资源本身
+/// 没有 schema 的资源不做校验
+#[derive(Clone, Default)]
+pub struct Schemas(Arc<HashMap<String, JSONSchema>>);
+
+impl Schemas {
+    /// 从文件中读取 schema, 顶层为资源名到 schema 的映射
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut schemas = HashMap::new();
+        for (name, schema) in storage::load(path)? {
+            let compiled = JSONSchema::compile(&schema)
+                .map_err(|e| format!("Invalid schema for {}: {}", name, e))?;
+            schemas.insert(name, compiled);
+        }
+        Ok(Schemas(Arc::new(schemas)))
+    }
+
+    /// 校验写入 name 的数据, 不符合 schema 时返回 422 和所有错误
+    pub fn validate(&self, name: &str, value: &Value) -> Result<(), status::Custom<Value>> {
+        let schema = match self.0.get(name) {
+            Some(schema) => schema,
+            None => return Ok(()),
+        };
+        if let Err(errors) = schema.validate(value) {
+            let violations: Vec<Value> = errors
+                .map(|e| json!({ "path": e.instance_path.to_string(), "message": e.to_string() }))
+                .collect();
+            return Err(error_with(
+                Status::UnprocessableEntity,
+                format!("Request body does not match the schema of {}", name),
+                json!({ "violations": violations }),
+            ));
+        }
+        Ok(())
+    }
+
+    /// 校验集合中的每一项
+    pub fn validate_items(&self, name: &str, items: &[Value]) -> Result<(), status::Custom<Value>> {
+        items.iter().try_for_each(|item| self.validate(name, item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn violations() {
+        let schema = json!({
+            "type": "object",
+            "required": ["title"],
+            "properties": {
+                "title": { "type": "string" },
+                "views": { "type": "integer", "minimum": 0 }
+            }
+        });
+        let schemas = Schemas(Arc::new(HashMap::from([(
+            String::from("posts"),
+            JSONSchema::compile(&schema).unwrap(),
+        )])));
+
+        assert!(schemas
+            .validate("posts", &json!({ "title": "rust" }))
+            .is_ok());
+        assert!(schemas.validate("comments", &json!({ "body": 1 })).is_ok());
+
+        let err = schemas
+            .validate("posts", &json!({ "views": -1 }))
+            .unwrap_err();
+        assert_eq!(err.0, Status::UnprocessableEntity);
+        let violations = err.1["details"]["violations"].as_array().unwrap();
+        assert_eq!(violations.len(), 2);
+        assert!(violations.iter().any(|v| v["path"] == "/views"));
+    }
+}
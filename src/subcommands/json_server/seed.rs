@@ -0,0 +1,319 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rocket::serde::json::serde_json::Map;
+use rocket::serde::json::Value;
+use std::collections::HashMap;
+
+const FIRST_NAMES: [&str; 20] = [
+    "James", "Mary", "John", "Linda", "Robert", "Emma", "Michael", "Olivia", "David", "Sophia",
+    "William", "Ava", "Daniel", "Mia", "Thomas", "Chloe", "Lucas", "Grace", "Henry", "Lily",
+];
+
+const LAST_NAMES: [&str; 20] = [
+    "Smith", "Johnson", "Brown", "Taylor", "Miller", "Wilson", "Moore", "Clark", "Lewis", "Walker",
+    "Hall", "Allen", "Young", "King", "Wright", "Scott", "Green", "Baker", "Adams", "Nelson",
+];
+
+const LOREM: &str = "lorem ipsum dolor sit amet consectetur adipiscing elit sed do eiusmod tempor \
+    incididunt ut labore et dolore magna aliqua enim ad minim veniam quis nostrud exercitation \
+    ullamco laboris nisi aliquip";
+
+/// 默认的日期范围
+const DATE_RANGE: &str = "2020-01-01..2025-12-31";
+
+/// 字段的生成方式, 模板中写作 `name` 或 `name:参数`
+#[derive(Debug, PartialEq)]
+enum Generator {
+    /// 全名, 如 Mary Smith
+    Name,
+    FirstName,
+    LastName,
+    /// 如 mary.smith12@example.com
+    Email,
+    /// int:1..100, 包含两端
+    Int(i64, i64),
+    /// float:0..100, 保留两位小数
+    Float(f64, f64),
+    Bool,
+    /// date 或 date:2020-01-01..2025-12-31, 格式为 YYYY-MM-DD
+    Date(i64, i64),
+    /// lorem 或 lorem:单词数
+    Lorem(usize),
+    /// pick:a|b|c, 从中随机选一个
+    Pick(Vec<String>),
+    /// ref:users, 随机引用另一个集合中的 id
+    Ref(String),
+    /// 模板中不是字符串的值原样输出
+    Literal(Value),
+}
+
+impl Generator {
+    fn parse(value: &Value) -> Result<Self, String> {
+        let spec = match value {
+            Value::String(spec) => spec,
+            value => return Ok(Generator::Literal(value.clone())),
+        };
+        let (kind, args) = match spec.split_once(':') {
+            Some((kind, args)) => (kind, Some(args)),
+            None => (spec.as_str(), None),
+        };
+        let generator = match (kind, args) {
+            ("name", None) => Generator::Name,
+            ("first_name", None) => Generator::FirstName,
+            ("last_name", None) => Generator::LastName,
+            ("email", None) => Generator::Email,
+            ("bool", None) => Generator::Bool,
+            ("int", Some(args)) => {
+                let (min, max) = parse_range(args, |v| v.parse::<i64>().ok())?;
+                Generator::Int(min, max)
+            }
+            ("float", Some(args)) => {
+                let (min, max) = parse_range(args, |v| v.parse::<f64>().ok())?;
+                Generator::Float(min, max)
+            }
+            ("date", args) => {
+                let (from, to) = parse_range(args.unwrap_or(DATE_RANGE), parse_date)?;
+                Generator::Date(from, to)
+            }
+            ("lorem", None) => Generator::Lorem(5),
+            ("lorem", Some(words)) => Generator::Lorem(
+                words
+                    .parse()
+                    .map_err(|_| format!("Invalid word count {}", words))?,
+            ),
+            ("pick", Some(choices)) => {
+                Generator::Pick(choices.split('|').map(String::from).collect())
+            }
+            ("ref", Some(collection)) => Generator::Ref(collection.to_string()),
+            _ => return Err(format!("Unknown generator {}", spec)),
+        };
+        Ok(generator)
+    }
+
+    fn generate(&self, rng: &mut StdRng, counts: &HashMap<&str, usize>) -> Result<Value, String> {
+        let value = match self {
+            Generator::Name => format!(
+                "{} {}",
+                FIRST_NAMES.choose(rng).unwrap(),
+                LAST_NAMES.choose(rng).unwrap()
+            )
+            .into(),
+            Generator::FirstName => FIRST_NAMES.choose(rng).unwrap().to_string().into(),
+            Generator::LastName => LAST_NAMES.choose(rng).unwrap().to_string().into(),
+            Generator::Email => format!(
+                "{}.{}{}@example.com",
+                FIRST_NAMES.choose(rng).unwrap().to_lowercase(),
+                LAST_NAMES.choose(rng).unwrap().to_lowercase(),
+                rng.gen_range(1..100)
+            )
+            .into(),
+            Generator::Int(min, max) => rng.gen_range(*min..=*max).into(),
+            Generator::Float(min, max) => {
+                ((rng.gen_range(*min..=*max) * 100.0).round() / 100.0).into()
+            }
+            Generator::Bool => rng.gen_bool(0.5).into(),
+            Generator::Date(from, to) => format_date(rng.gen_range(*from..=*to)).into(),
+            Generator::Lorem(words) => {
+                let lorem: Vec<&str> = LOREM.split_whitespace().collect();
+                (0..*words)
+                    .map(|_| *lorem.choose(rng).unwrap())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .into()
+            }
+            Generator::Pick(choices) => choices.choose(rng).unwrap().clone().into(),
+            Generator::Ref(collection) => match counts.get(collection.as_str()) {
+                Some(0) | None => return Err(format!("Unknown collection {}", collection)),
+                Some(count) => rng.gen_range(1..=*count).into(),
+            },
+            Generator::Literal(value) => value.clone(),
+        };
+        Ok(value)
+    }
+}
+
+/// 解析 min..max, min 不能大于 max
+fn parse_range<T: PartialOrd>(
+    range: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<(T, T), String> {
+    let invalid = || format!("Invalid range {}", range);
+    let (min, max) = range.split_once("..").ok_or_else(invalid)?;
+    let (min, max) = (
+        parse(min).ok_or_else(invalid)?,
+        parse(max).ok_or_else(invalid)?,
+    );
+    if min > max {
+        return Err(invalid());
+    }
+    Ok((min, max))
+}
+
+/// 解析 YYYY-MM-DD 为距离 1970-01-01 的天数
+fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = (month + 9) % 12;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146097 + day_of_era - 719468)
+}
+
+/// 将距离 1970-01-01 的天数格式化为 YYYY-MM-DD
+fn format_date(days: i64) -> String {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// 按模板生成数据, 相同的模板和 seed 生成相同的数据
+/// 模板中每个资源为 {"count": 数量, "fields": {字段: 生成方式}}
+/// 有 count 时生成集合, id 从 1 开始递增; 没有 count 时生成单个对象
+pub fn generate(
+    template: &HashMap<String, Value>,
+    seed: u64,
+    id_field: &str,
+) -> Result<HashMap<String, Value>, String> {
+    // HashMap 的顺序不固定, 按名称排序后再生成
+    let mut names: Vec<&String> = template.keys().collect();
+    names.sort();
+    let counts: HashMap<&str, usize> = names
+        .iter()
+        .filter_map(|name| Some((name.as_str(), template[*name]["count"].as_u64()? as usize)))
+        .collect();
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut db = HashMap::new();
+    for name in names {
+        let resource = &template[name];
+        let fields = match &resource["fields"] {
+            Value::Object(fields) => fields,
+            _ => return Err(format!("Resource {} has no fields", name)),
+        };
+        let generators = fields
+            .iter()
+            .map(|(field, spec)| {
+                Generator::parse(spec)
+                    .map(|generator| (field, generator))
+                    .map_err(|e| format!("{}.{}: {}", name, field, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut item = |id: Option<usize>| -> Result<Value, String> {
+            let mut item = Map::new();
+            if let Some(id) = id {
+                item.insert(id_field.to_string(), id.into());
+            }
+            for (field, generator) in &generators {
+                let value = generator
+                    .generate(&mut rng, &counts)
+                    .map_err(|e| format!("{}.{}: {}", name, field, e))?;
+                item.insert(field.to_string(), value);
+            }
+            Ok(Value::Object(item))
+        };
+        let value = match counts.get(name.as_str()) {
+            Some(count) => Value::Array(
+                (1..=*count)
+                    .map(|id| item(Some(id)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            None => item(None)?,
+        };
+        db.insert(name.clone(), value);
+    }
+    Ok(db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::serde::json::serde_json::{self, json};
+
+    fn template() -> HashMap<String, Value> {
+        serde_json::from_value(json!({
+            "users": {
+                "count": 5,
+                "fields": {
+                    "name": "name",
+                    "email": "email",
+                    "age": "int:18..65",
+                    "birthday": "date:1990-01-01..1999-12-31",
+                    "role": "pick:admin|editor"
+                }
+            },
+            "posts": {
+                "count": 20,
+                "fields": { "title": "lorem:3", "userId": "ref:users", "draft": false }
+            },
+            "profile": { "fields": { "name": "name" } }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn deterministic() {
+        let db = generate(&template(), 42, "id").unwrap();
+        assert_eq!(db, generate(&template(), 42, "id").unwrap());
+        assert_ne!(db, generate(&template(), 7, "id").unwrap());
+
+        let users = db["users"].as_array().unwrap();
+        assert_eq!(users.len(), 5);
+        assert_eq!(users[4]["id"], 5);
+        for user in users {
+            assert!((18..=65).contains(&user["age"].as_i64().unwrap()));
+            assert!(user["email"].as_str().unwrap().ends_with("@example.com"));
+            assert!(user["birthday"].as_str().unwrap().starts_with("199"));
+            assert!(["admin", "editor"].contains(&user["role"].as_str().unwrap()));
+        }
+        for post in db["posts"].as_array().unwrap() {
+            assert!((1..=5).contains(&post["userId"].as_u64().unwrap()));
+            assert_eq!(post["title"].as_str().unwrap().split(' ').count(), 3);
+            assert_eq!(post["draft"], false);
+        }
+        assert!(db["profile"]["name"].is_string());
+    }
+
+    #[test]
+    fn invalid_template() {
+        let mut template = template();
+        template.insert(
+            String::from("tags"),
+            json!({ "count": 1, "fields": { "name": "color" } }),
+        );
+        assert_eq!(
+            generate(&template, 0, "id").unwrap_err(),
+            "tags.name: Unknown generator color"
+        );
+        assert!(Generator::parse(&json!("int:10..1")).is_err());
+        assert!(Generator::parse(&json!("ref:comments"))
+            .unwrap()
+            .generate(&mut StdRng::seed_from_u64(0), &HashMap::new())
+            .is_err());
+    }
+
+    #[test]
+    fn dates() {
+        for date in ["1970-01-01", "2000-02-29", "2024-12-31", "1969-07-20"] {
+            assert_eq!(format_date(parse_date(date).unwrap()), date);
+        }
+        assert_eq!(parse_date("1970-01-02"), Some(1));
+        assert_eq!(parse_date("2024-13-01"), None);
+    }
+}
@@ -0,0 +1,382 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::serde::json::{serde_json, Value};
+use rocket::tokio::{sync::Notify, task, time};
+use rocket::{Orbit, Rocket};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::{collections::HashMap, time::Duration};
+
+/// 数据持久化, 只读模式下修改只保存在内存中
+/// 修改不会立即写入文件, 而是由后台任务在 debounce 时间内没有新修改后合并写入
+/// 同时作为 Fairing, 启动时开启后台写入任务, 关闭时写入剩余的数据
+#[derive(Clone)]
+pub struct Storage(Arc<Inner>);
+
+struct Inner {
+    /// 写入的文件路径, 只读模式下为 None
+    path: Option<PathBuf>,
+    /// 最后一次修改后等待多久再写入
+    debounce: Duration,
+    /// 等待写入的数据, 只保留最新的一份
+    pending: Mutex<Option<HashMap<String, Value>>>,
+    /// 有新的数据等待写入时通知后台任务
+    notify: Notify,
+    /// 保证同一时间只有一个写入, 且后取出的数据后写入
+    io_lock: Mutex<()>,
+    /// 最后一次写入内容的哈希, 用于区分文件变化是否由自己写入
+    last_written: Mutex<Option<u64>>,
+    /// 最后一次写入失败的原因, 写入成功后清除
+    write_error: Mutex<Option<String>>,
+}
+
+impl Storage {
+    /// 写入到指定文件
+    pub fn new(path: PathBuf, debounce: Duration) -> Self {
+        Storage::with_path(Some(path), debounce)
+    }
+
+    /// 只读模式, 不写入文件
+    pub fn read_only() -> Self {
+        Storage::with_path(None, Duration::ZERO)
+    }
+
+    fn with_path(path: Option<PathBuf>, debounce: Duration) -> Self {
+        Storage(Arc::new(Inner {
+            path,
+            debounce,
+            pending: Mutex::new(None),
+            notify: Notify::new(),
+            io_lock: Mutex::new(()),
+            last_written: Mutex::new(None),
+            write_error: Mutex::new(None),
+        }))
+    }
+
+    /// 记录需要写入的数据, 由后台任务写入文件
+    /// 这里只复制数据, 序列化和写文件都不会占用 db 的锁
+    pub fn write(&self, db: &HashMap<String, Value>) {
+        if self.0.path.is_none() {
+            return;
+        }
+        *self.0.pending.lock().unwrap() = Some(db.clone());
+        self.0.notify.notify_one();
+    }
+
+    /// 检查数据能否写入文件, 写入的格式不支持时返回错误信息, 只读模式下总是可以写入
+    pub fn check(&self, value: &Value) -> Result<(), String> {
+        match &self.0.path {
+            Some(path) => Format::from_path(path).check(value),
+            None => Ok(()),
+        }
+    }
+
+    /// 最后一次写入失败的原因, 写入成功前修改请求会返回这个错误
+    pub fn write_error(&self) -> Option<String> {
+        self.0.write_error.lock().unwrap().clone()
+    }
+
+    /// 立即写入等待中的数据, 返回是否写入成功
+    /// 取出数据和写入都在 io_lock 中进行, 保证后取出的数据后写入
+    /// 写入失败时数据放回等待中, 没有更新的数据时由下一次写入重试
+    pub fn flush(&self) -> bool {
+        let path = match &self.0.path {
+            Some(path) => path,
+            None => return true,
+        };
+        let _guard = self.0.io_lock.lock().unwrap();
+        let db = match self.0.pending.lock().unwrap().take() {
+            Some(db) => db,
+            None => return true,
+        };
+        match write_atomic(path, &db) {
+            Ok(content) => {
+                *self.0.last_written.lock().unwrap() = Some(hash(&content));
+                self.0.write_error.lock().unwrap().take();
+                true
+            }
+            Err(e) => {
+                let message = format!("Unable to write file {}: {}", path.display(), e);
+                eprintln!("{}", message);
+                *self.0.write_error.lock().unwrap() = Some(message);
+                self.0.pending.lock().unwrap().get_or_insert(db);
+                false
+            }
+        }
+    }
+
+    /// 丢弃等待中的数据, 文件被外部修改并重新加载后, 旧数据不应再覆盖文件
+    /// 只在 reload_external 的 reload 中调用, 此时已经持有 io_lock
+    pub fn discard(&self) {
+        self.0.pending.lock().unwrap().take();
+    }
+
+    /// 读取被修改的文件, 内容不是自己写入的才调用 reload
+    /// 读取和判断都在 io_lock 中进行, 正在写入的内容不会被当作外部修改
+    pub fn reload_external(&self, path: &Path, reload: impl FnOnce(&str)) -> io::Result<()> {
+        let _guard = self.0.io_lock.lock().unwrap();
+        let content = fs::read_to_string(path)?;
+        if !self.is_last_written(&content) {
+            reload(&content);
+        }
+        Ok(())
+    }
+
+    /// 判断内容是否是最后一次写入的内容
+    fn is_last_written(&self, content: &str) -> bool {
+        *self.0.last_written.lock().unwrap() == Some(hash(content))
+    }
+}
+
+/// 计算内容的哈希
+fn hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 数据文件格式, 由扩展名决定, 其他扩展名都按 JSON 处理
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Json5,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        match extension.to_lowercase().as_str() {
+            "json5" => Format::Json5,
+            "yaml" | "yml" => Format::Yaml,
+            "toml" => Format::Toml,
+            _ => Format::Json,
+        }
+    }
+
+    /// 解析文件内容, 顶层必须是对象
+    pub fn parse(self, data: &str) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+        Ok(match self {
+            Format::Json => serde_json::from_str(data)?,
+            Format::Json5 => json5::from_str(data)?,
+            Format::Yaml => serde_yaml::from_str(data)?,
+            Format::Toml => toml::from_str(data)?,
+        })
+    }
+
+    /// 序列化为文件内容, 资源按名称排序, 相同的数据写入相同的内容
+    /// JSON5 写入为 JSON, 注释不会保留; TOML 不支持 null, 包含 null 时返回错误
+    pub fn serialize(self, db: &HashMap<String, Value>) -> Result<String, Box<dyn Error>> {
+        let db: BTreeMap<&String, &Value> = db.iter().collect();
+        Ok(match self {
+            Format::Json | Format::Json5 => serde_json::to_string_pretty(&db)?,
+            Format::Yaml => serde_yaml::to_string(&db)?,
+            Format::Toml => {
+                if db.values().any(|value| contains_null(value)) {
+                    return Err(TOML_NULL.into());
+                }
+                toml::to_string_pretty(&db)?
+            }
+        })
+    }
+
+    /// 检查数据能否写入这种格式, 目前只有 TOML 有限制
+    /// TOML 不支持 null, 其他值按 TOML 序列化一次, 如超出范围的整数会返回错误
+    pub fn check(self, value: &Value) -> Result<(), String> {
+        match self {
+            Format::Toml if contains_null(value) => Err(TOML_NULL.to_string()),
+            Format::Toml => toml::to_string(&BTreeMap::from([("value", value)]))
+                .map(|_| ())
+                .map_err(|e| format!("TOML files cannot store this value: {}", e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+const TOML_NULL: &str = "TOML files cannot store null values";
+
+/// 对象和数组中是否包含 null
+fn contains_null(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Object(map) => map.values().any(contains_null),
+        Value::Array(items) => items.iter().any(contains_null),
+        _ => false,
+    }
+}
+
+/// 从文件中读取数据, 按扩展名解析, 顶层必须是对象
+pub fn load(path: &Path) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let data = fs::read_to_string(path)?;
+    Format::from_path(path).parse(&data)
+}
+
+/// 修改不停时, 第一次修改后最多等待多少倍的 debounce 再写入
+const MAX_WAIT_FACTOR: u32 = 10;
+
+/// 写入失败后重试的最短间隔
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[rocket::async_trait]
+impl Fairing for Storage {
+    fn info(&self) -> Info {
+        Info {
+            name: "json-server storage",
+            kind: Kind::Liftoff | Kind::Shutdown,
+        }
+    }
+
+    async fn on_liftoff(&self, _rocket: &Rocket<Orbit>) {
+        if self.0.path.is_none() {
+            return;
+        }
+        let storage = self.clone();
+        rocket::tokio::spawn(async move {
+            loop {
+                storage.0.notify.notified().await;
+                // 等待期间有新的修改则重新计时, 最后一次修改后 debounce 内没有修改才写入
+                // 修改一直不停时, 最多等待 MAX_WAIT_FACTOR 倍的 debounce 也会写入
+                let deadline = time::Instant::now() + storage.0.debounce * MAX_WAIT_FACTOR;
+                loop {
+                    let wait = deadline.saturating_duration_since(time::Instant::now());
+                    let wait = wait.min(storage.0.debounce);
+                    if wait.is_zero()
+                        || time::timeout(wait, storage.0.notify.notified())
+                            .await
+                            .is_err()
+                    {
+                        break;
+                    }
+                }
+                let flushed = {
+                    let storage = storage.clone();
+                    task::spawn_blocking(move || storage.flush()).await
+                };
+                // 写入失败时稍后重试, 直到写入成功
+                if !matches!(flushed, Ok(true)) {
+                    time::sleep(storage.0.debounce.max(RETRY_DELAY)).await;
+                    storage.0.notify.notify_one();
+                }
+            }
+        });
+    }
+
+    async fn on_shutdown(&self, _rocket: &Rocket<Orbit>) {
+        let storage = self.clone();
+        let _ = task::spawn_blocking(move || storage.flush()).await;
+    }
+}
+
+/// 按扩展名对应的格式写入
+/// 先写入临时文件, 再重命名为目标文件, 避免写入中断导致文件损坏
+/// 返回写入的内容
+pub fn write_atomic(path: &Path, db: &HashMap<String, Value>) -> io::Result<String> {
+    let content = Format::from_path(path)
+        .serialize(db)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    let tmp_path = path.with_file_name(file_name);
+
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::serde::json::serde_json::json;
+
+    #[test]
+    fn flush_pending() {
+        let path =
+            std::env::temp_dir().join(format!("json_server_storage_{}.json", std::process::id()));
+        let storage = Storage::new(path.clone(), Duration::ZERO);
+        let mut db = HashMap::new();
+
+        db.insert(String::from("profile"), json!({ "name": "typicode" }));
+        storage.write(&db);
+        db.insert(String::from("profile"), json!({ "name": "rust" }));
+        storage.write(&db);
+        assert!(storage.flush());
+
+        let data = fs::read_to_string(&path).unwrap();
+        assert!(storage.is_last_written(&data));
+        assert_eq!(load(&path).unwrap(), db);
+
+        // 自己写入的内容不会重新加载, 外部修改的内容会
+        let mut reloaded = None;
+        storage
+            .reload_external(&path, |content| reloaded = Some(content.to_string()))
+            .unwrap();
+        assert_eq!(reloaded, None);
+        fs::write(&path, "{}").unwrap();
+        storage
+            .reload_external(&path, |content| reloaded = Some(content.to_string()))
+            .unwrap();
+        assert_eq!(reloaded.as_deref(), Some("{}"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn write_error() {
+        let path = std::env::temp_dir()
+            .join(format!("json_server_missing_{}", std::process::id()))
+            .join("db.json");
+        let storage = Storage::new(path, Duration::ZERO);
+        storage.write(&HashMap::new());
+        // 写入失败时记录错误, 数据保留等待重试
+        assert!(!storage.flush());
+        assert!(storage.write_error().is_some());
+        assert!(storage.0.pending.lock().unwrap().is_some());
+    }
+
+    #[test]
+    fn formats() {
+        let db: HashMap<String, Value> = serde_json::from_value(json!({
+            "posts": [{ "id": 1, "title": "json-server", "tags": ["a", "b"] }],
+            "profile": { "name": "typicode" }
+        }))
+        .unwrap();
+        for format in [Format::Json, Format::Json5, Format::Yaml, Format::Toml] {
+            let content = format.serialize(&db).unwrap();
+            assert_eq!(format.parse(&content).unwrap(), db, "{:?}", format);
+        }
+
+        let json5 = "{ // comment\n posts: [{ id: 1, title: 'json-server', }], }";
+        assert_eq!(
+            Format::Json5.parse(json5).unwrap()["posts"],
+            json!([{ "id": 1, "title": "json-server" }])
+        );
+        assert_eq!(Format::from_path(Path::new("db.YML")), Format::Yaml);
+        assert_eq!(Format::from_path(Path::new("db")), Format::Json);
+
+        let profile = json!({ "name": null, "age": 1 });
+        assert!(Format::Toml.check(&profile).is_err());
+        assert!(Format::Yaml.check(&profile).is_ok());
+        assert!(Format::Toml.check(&json!(u64::MAX)).is_err());
+        assert!(Format::Toml.check(&json!({ "age": 1 })).is_ok());
+        let db = HashMap::from([(String::from("profile"), profile)]);
+        assert!(Format::Toml.serialize(&db).is_err());
+        assert!(Format::Yaml.serialize(&db).is_ok());
+    }
+
+    #[test]
+    fn read_only() {
+        let storage = Storage::read_only();
+        storage.write(&HashMap::new());
+        assert!(storage.0.pending.lock().unwrap().is_none());
+    }
+}
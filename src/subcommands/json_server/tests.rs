@@ -0,0 +1,619 @@
+//! 通过 local client 测试所有路由, 数据文件写在临时目录中
+
+use super::storage;
+use crate::cli::{Cli, Commands};
+use clap::Parser;
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::blocking::{Client, LocalResponse};
+use rocket::serde::json::serde_json::{self, json};
+use rocket::serde::json::Value;
+use rocket::{Build, Rocket};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 临时目录中的数据文件, 测试结束后删除
+struct TempDir(PathBuf);
+
+impl TempDir {
+    /// 测试并行运行, 每个测试使用不同的目录
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("json_server_tests_{}_{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    fn write(&self, file_name: &str, value: &Value) -> PathBuf {
+        let path = self.0.join(file_name);
+        fs::write(&path, value.to_string()).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn db() -> Value {
+    json!({
+        "posts": [
+            { "id": 1, "title": "json-server", "views": 10 },
+            { "id": "2", "title": "rust", "views": 20 }
+        ],
+        "comments": [
+            { "id": 1, "body": "a", "postId": 1 },
+            { "id": 2, "body": "b", "postId": "2" }
+        ],
+        "profile": { "name": "typicode" }
+    })
+}
+
+/// 按命令行参数构建 rocket, 参数与 `rust_tools json-server` 相同
+fn build(args: &[&str]) -> Result<Rocket<Build>, Box<dyn Error>> {
+    let cli = Cli::try_parse_from(["rust_tools", "json-server"].iter().chain(args))?;
+    let Commands::JsonServer(args) = cli.command else {
+        unreachable!()
+    };
+    args.rocket()
+}
+
+fn start(path: &Path, args: &[&str]) -> Client {
+    let path = path.to_str().unwrap();
+    let args: Vec<&str> = [path].iter().chain(args).copied().collect();
+    Client::tracked(build(&args).unwrap()).unwrap()
+}
+
+fn json(response: LocalResponse) -> Value {
+    response.into_json().unwrap()
+}
+
+/// 等待文件写入, 超时返回最后读到的数据
+fn wait_for_file(path: &Path, expected: impl Fn(&Value) -> bool) -> Value {
+    let start = Instant::now();
+    loop {
+        let data = storage::load(path)
+            .map(|db| serde_json::to_value(db).unwrap())
+            .unwrap_or_default();
+        if expected(&data) || start.elapsed() > Duration::from_secs(5) {
+            return data;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn storage_value(path: &Path) -> Value {
+    serde_json::to_value(storage::load(path).unwrap()).unwrap()
+}
+
+#[test]
+fn read_routes() {
+    let dir = TempDir::new("read_routes");
+    let client = start(&dir.write("db.json", &db()), &["--read-only"]);
+
+    assert_eq!(json(client.get("/db").dispatch()), db());
+    assert_eq!(json(client.get("/posts").dispatch()), db()["posts"]);
+    assert_eq!(json(client.get("/profile").dispatch()), db()["profile"]);
+    assert_eq!(
+        json(client.get("/posts?views_gte=15").dispatch()),
+        json!([{ "id": "2", "title": "rust", "views": 20 }])
+    );
+
+    let response = client.get("/posts?_page=1&_limit=1").dispatch();
+    assert_eq!(response.headers().get_one("X-Total-Count"), Some("2"));
+    assert_eq!(json(response)[0]["id"], json!(1));
+
+    assert_eq!(
+        json(client.get("/posts/1/comments").dispatch()),
+        json!([{ "id": 1, "body": "a", "postId": 1 }])
+    );
+    assert_eq!(
+        json(client.get("/posts/2?_embed=comments").dispatch())["comments"],
+        json!([{ "id": 2, "body": "b", "postId": "2" }])
+    );
+    assert_eq!(
+        json(client.get("/comments/2?_expand=post").dispatch())["post"]["title"],
+        json!("rust")
+    );
+}
+
+#[test]
+fn mixed_ids() {
+    let dir = TempDir::new("mixed_ids");
+    let client = start(&dir.write("db.json", &db()), &["--read-only"]);
+
+    // 数字 id 和字符串 id 都可以用路径中的 id 查找
+    assert_eq!(
+        json(client.get("/posts/1").dispatch())["title"],
+        json!("json-server")
+    );
+    assert_eq!(
+        json(client.get("/posts/2").dispatch())["title"],
+        json!("rust")
+    );
+
+    // 插入时 1 与 "1", "2" 与 2 视为相同的 id
+    for id in [json!("1"), json!(2)] {
+        let response = client
+            .post("/posts")
+            .json(&json!({ "id": id, "title": "duplicate" }))
+            .dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+        assert_eq!(json(response)["details"]["id"], id);
+    }
+
+    // 自增 id 也会计算字符串形式的数字 id
+    let response = client
+        .post("/posts")
+        .json(&json!({ "title": "new" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    assert_eq!(json(response)["id"], json!(3));
+
+    let response = client
+        .put("/posts/2")
+        .json(&json!({ "title": "put" }))
+        .dispatch();
+    assert_eq!(json(response), json!({ "id": "2", "title": "put" }));
+    assert_eq!(
+        client.delete("/posts/2").dispatch().status(),
+        Status::NoContent
+    );
+    assert_eq!(client.get("/posts/2").dispatch().status(), Status::NotFound);
+}
+
+#[test]
+fn write_routes() {
+    let dir = TempDir::new("write_routes");
+    let client = start(&dir.write("db.json", &db()), &["--read-only"]);
+
+    let response = client
+        .post("/posts")
+        .json(&json!({ "title": "new" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    assert_eq!(response.headers().get_one("Location"), Some("/posts/3"));
+
+    let response = client
+        .post("/posts/1/comments")
+        .json(&json!({ "body": "nested" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    assert_eq!(json(response)["postId"], json!(1));
+
+    let response = client
+        .post("/tags")
+        .json(&json!({ "name": "a" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    assert_eq!(
+        json(client.get("/tags").dispatch()),
+        json!([{ "id": 1, "name": "a" }])
+    );
+    assert_eq!(
+        client.delete("/tags").dispatch().status(),
+        Status::NoContent
+    );
+    assert_eq!(client.get("/tags").dispatch().status(), Status::NotFound);
+
+    let response = client
+        .put("/settings")
+        .json(&json!({ "theme": "dark" }))
+        .dispatch();
+    assert_eq!(json(response), json!({ "theme": "dark" }));
+    let response = client
+        .put("/profile")
+        .json(&json!({ "name": "put" }))
+        .dispatch();
+    assert_eq!(json(response), json!({ "name": "put" }));
+    // 对象资源的 POST 直接替换原数据
+    let response = client
+        .post("/profile")
+        .json(&json!({ "name": "rust" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    assert_eq!(
+        json(client.get("/profile").dispatch()),
+        json!({ "name": "rust" })
+    );
+
+    let response = client
+        .patch("/posts/1")
+        .json(&json!({ "views": null, "draft": true }))
+        .dispatch();
+    assert_eq!(
+        json(response),
+        json!({ "id": 1, "title": "json-server", "draft": true })
+    );
+    let response = client
+        .patch("/profile")
+        .json(&json!({ "age": 1 }))
+        .dispatch();
+    assert_eq!(json(response), json!({ "name": "rust", "age": 1 }));
+
+    let json_patch = ContentType::new("application", "json-patch+json");
+    let response = client
+        .patch("/posts/1")
+        .header(json_patch.clone())
+        .body(r#"[{ "op": "replace", "path": "/title", "value": "patched" }]"#)
+        .dispatch();
+    assert_eq!(json(response)["title"], json!("patched"));
+    let response = client
+        .patch("/profile")
+        .header(json_patch.clone())
+        .body(r#"[{ "op": "test", "path": "/name", "value": "typicode" }]"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+
+    // 修改集合后每个数据都必须是有 id 的对象
+    for patch in [
+        r#"[{ "op": "add", "path": "/-", "value": 5 }]"#,
+        r#"[{ "op": "add", "path": "/-", "value": { "title": "no id" } }]"#,
+        r#"[{ "op": "remove", "path": "/0/id" }]"#,
+    ] {
+        let response = client
+            .patch("/posts")
+            .header(json_patch.clone())
+            .body(patch)
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity, "{}", patch);
+    }
+    let response = client
+        .patch("/posts")
+        .header(json_patch.clone())
+        .body(r#"[{ "op": "add", "path": "/-", "value": { "id": 9 } }]"#)
+        .dispatch();
+    assert_eq!(
+        json(response).as_array().unwrap().last(),
+        Some(&json!({ "id": 9 }))
+    );
+}
+
+#[test]
+fn missing_resources() {
+    let dir = TempDir::new("missing_resources");
+    let client = start(&dir.write("db.json", &db()), &["--read-only"]);
+
+    for response in [
+        client.get("/missing").dispatch(),
+        client.get("/missing/1").dispatch(),
+        client.get("/posts/99").dispatch(),
+        client.get("/missing/1/comments").dispatch(),
+        client.put("/posts/99").json(&json!({})).dispatch(),
+        client.patch("/posts/99").json(&json!({})).dispatch(),
+        client.delete("/posts/99").dispatch(),
+        client.delete("/missing").dispatch(),
+    ] {
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(json(response)["status"], json!(404));
+    }
+
+    // 集合和单个资源不能混用
+    assert_eq!(
+        client.get("/profile/1").dispatch().status(),
+        Status::BadRequest
+    );
+    assert_eq!(
+        client.put("/posts").json(&json!({})).dispatch().status(),
+        Status::BadRequest
+    );
+
+    let response = client
+        .post("/posts")
+        .header(ContentType::JSON)
+        .body("{ invalid")
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(
+        client.post("/posts").json(&json!([1])).dispatch().status(),
+        Status::UnprocessableEntity
+    );
+}
+
+#[test]
+fn persistence() {
+    let dir = TempDir::new("persistence");
+    let path = dir.write("db.json", &db());
+    let client = start(&path, &["--debounce", "0"]);
+    client
+        .post("/posts")
+        .json(&json!({ "title": "saved" }))
+        .dispatch();
+    let data = wait_for_file(&path, |data| {
+        data["posts"]
+            .as_array()
+            .is_some_and(|posts| posts.len() == 3)
+    });
+    assert_eq!(data["posts"][2], json!({ "id": 3, "title": "saved" }));
+    client.delete("/posts/1").dispatch();
+    let data = wait_for_file(&path, |data| data["posts"][0]["id"] == json!("2"));
+    assert_eq!(data["posts"].as_array().unwrap().len(), 2);
+
+    // --out 写入另一个文件, 原文件不变
+    let path = dir.write("out.json", &db());
+    let out = dir.0.join("db.out.json");
+    let client = start(&path, &["--debounce", "0", "--out", out.to_str().unwrap()]);
+    client.delete("/comments").dispatch();
+    let data = wait_for_file(&out, |data| {
+        data.get("comments").is_none() && data.get("posts").is_some()
+    });
+    assert_eq!(
+        data,
+        json!({ "posts": db()["posts"], "profile": db()["profile"] })
+    );
+    assert_eq!(json(client.get("/db").dispatch()), data);
+    assert_eq!(storage::load(&path).unwrap()["comments"], db()["comments"]);
+
+    // --read-only 只修改内存中的数据
+    let path = dir.write("read_only.json", &db());
+    let client = start(&path, &["--read-only"]);
+    client.delete("/profile").dispatch();
+    assert_eq!(client.get("/profile").dispatch().status(), Status::NotFound);
+    assert!(!dir.0.join("read_only.json.tmp").exists());
+    assert_eq!(
+        serde_json::to_value(storage::load(&path).unwrap()).unwrap(),
+        db()
+    );
+
+    // 启动时读取上次写入的数据
+    let path = dir.0.join("db.json");
+    let client = start(&path, &["--read-only"]);
+    assert_eq!(
+        json(client.get("/posts/3").dispatch())["title"],
+        json!("saved")
+    );
+
+    // TOML 不支持 null, 包含 null 的写入返回 422
+    let path = dir.0.join("db.toml");
+    fs::write(&path, "[[posts]]\nid = 1\ntitle = \"toml\"\n").unwrap();
+    let client = start(&path, &["--debounce", "0"]);
+    let response = client
+        .patch("/posts/1")
+        .json(&json!({ "tags": [null] }))
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    assert_eq!(
+        json(client.get("/posts/1").dispatch()),
+        json!({ "id": 1, "title": "toml" })
+    );
+    // 超出 TOML 整数范围的值同样返回 422, 之后的写入仍然可以写入文件
+    let response = client
+        .post("/posts")
+        .json(&json!({ "n": u64::MAX }))
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let response = client
+        .post("/posts")
+        .json(&json!({ "title": "saved" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    let data = wait_for_file(&path, |data| {
+        data["posts"]
+            .as_array()
+            .is_some_and(|posts| posts.len() == 2)
+    });
+    assert_eq!(data["posts"][1], json!({ "id": 2, "title": "saved" }));
+}
+
+#[test]
+fn debounce_max_wait() {
+    let dir = TempDir::new("debounce_max_wait");
+    let path = dir.write("db.json", &db());
+    let client = start(&path, &["--debounce", "100"]);
+    // 修改间隔小于 debounce 时, 最多 10 倍的 debounce 后也会写入
+    let start = Instant::now();
+    let written = loop {
+        client
+            .patch("/profile")
+            .json(&json!({ "age": 1 }))
+            .dispatch();
+        if storage_value(&path)["profile"]["age"] == json!(1) {
+            break true;
+        }
+        if start.elapsed() > Duration::from_secs(5) {
+            break false;
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    assert!(written);
+}
+
+#[test]
+fn other_routes() {
+    let dir = TempDir::new("other_routes");
+    let routes = dir.write("routes.json", &json!({ "/api/*": "/$1" }));
+    let schema = dir.write(
+        "schema.json",
+        &json!({ "posts": { "type": "object", "properties": { "title": { "type": "string" } } } }),
+    );
+    let client = start(
+        &dir.write("db.json", &db()),
+        &[
+            "--read-only",
+            "--routes",
+            routes.to_str().unwrap(),
+            "--schema",
+            schema.to_str().unwrap(),
+        ],
+    );
+
+    assert_eq!(
+        json(client.get("/api/posts/1").dispatch())["title"],
+        json!("json-server")
+    );
+    let response = client
+        .post("/posts")
+        .json(&json!({ "title": 1 }))
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    // 替换整个集合返回 400, 而不是校验失败的 422
+    let response = client.put("/posts").json(&json!({ "title": 1 })).dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let response = client
+        .post("/graphql")
+        .json(&json!({ "query": r#"{ post(id: "2") { title } profile { name } }"# }))
+        .dispatch();
+    assert_eq!(
+        json(response)["data"],
+        json!({ "post": { "title": "rust" }, "profile": { "name": "typicode" } })
+    );
+    let document = json(client.get("/openapi.json").dispatch());
+    assert!(document["paths"]["/posts/{id}"].is_object());
+
+    for uri in ["/graphql", "/__docs", "/", "/__ui/posts", "/__ui/posts/1"] {
+        let response = client.get(uri).dispatch();
+        assert_eq!(response.status(), Status::Ok, "{}", uri);
+        assert_eq!(response.content_type(), Some(ContentType::HTML), "{}", uri);
+    }
+    let response = client.get("/__events?resource=posts").dispatch();
+    assert_eq!(response.content_type(), Some(ContentType::EventStream));
+
+    // 快照和重置
+    assert_eq!(
+        client.post("/__admin/snapshots/start").dispatch().status(),
+        Status::Created
+    );
+    client.delete("/posts/1").dispatch();
+    client.post("/__admin/snapshots/start/restore").dispatch();
+    assert_eq!(client.get("/posts/1").dispatch().status(), Status::Ok);
+    client.delete("/posts/1").dispatch();
+    client.post("/__admin/reset").dispatch();
+    assert_eq!(json(client.get("/db").dispatch()), db());
+
+    let response = client
+        .get("/posts")
+        .header(Header::new("X-Mock-Delay", "0"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn mount_prefixes() {
+    let dir = TempDir::new("mount_prefixes");
+    let users = dir.write(
+        "users.json",
+        &json!({ "users": [{ "id": 1, "name": "a" }] }),
+    );
+    let shop = dir.write(
+        "shop.json",
+        &json!({ "products": [{ "id": 1, "name": "b" }] }),
+    );
+    let users_db = format!("{}@/users-api", users.display());
+    let shop_db = format!("{}@/shop/", shop.display());
+    let args = ["--db", &users_db, "--db", &shop_db, "--debounce", "0"];
+    let client = Client::tracked(build(&args).unwrap()).unwrap();
+
+    assert_eq!(
+        json(client.get("/users-api/users/1").dispatch())["name"],
+        json!("a")
+    );
+    assert_eq!(
+        json(client.get("/shop/db").dispatch()),
+        storage_value(&shop)
+    );
+    for uri in ["/users-api/products", "/shop/users", "/users", "/db"] {
+        assert_eq!(
+            client.get(uri).dispatch().status(),
+            Status::NotFound,
+            "{}",
+            uri
+        );
+    }
+
+    // 每个数据文件写回自己的文件
+    let response = client
+        .post("/shop/products")
+        .json(&json!({ "name": "c" }))
+        .dispatch();
+    assert_eq!(
+        response.headers().get_one("Location"),
+        Some("/shop/products/2")
+    );
+    let data = wait_for_file(&shop, |data| {
+        data["products"].as_array().is_some_and(|p| p.len() == 2)
+    });
+    assert_eq!(data["products"][1], json!({ "id": 2, "name": "c" }));
+    assert_eq!(
+        storage_value(&users),
+        json!({ "users": [{ "id": 1, "name": "a" }] })
+    );
+
+    let response = client.post("/users-api/__admin/snapshots/start").dispatch();
+    assert_eq!(
+        response.headers().get_one("Location"),
+        Some("/users-api/__admin/snapshots/start")
+    );
+    client.post("/shop/__admin/reset").dispatch();
+    assert_eq!(
+        client.get("/shop/products/2").dispatch().status(),
+        Status::NotFound
+    );
+    assert_eq!(
+        json(client.get("/shop/openapi.json").dispatch())["servers"],
+        json!([{ "url": "/shop" }])
+    );
+    let html = client.get("/shop").dispatch().into_string().unwrap();
+    assert!(html.contains(r#"href="/shop/__ui/products""#));
+    assert!(html.contains("&#x2F;shop&#x2F;products"));
+    assert!(!html.contains("&#x2F;users-api"));
+    let response = client.post("/users-api/__ui/users/1/delete").dispatch();
+    assert_eq!(
+        response.headers().get_one("Location"),
+        Some("/users-api/__ui/users")
+    );
+
+    let error = build(&["--db", &users_db, "--db", "v2.json@/users-api/v2"]).unwrap_err();
+    assert!(error.to_string().contains("overlap"), "{}", error);
+    assert!(build(&[users.to_str().unwrap(), "--db", &shop_db]).is_err());
+}
+
+#[test]
+fn replay_without_database() {
+    let dir = TempDir::new("replay");
+    let fixtures = dir.write(
+        "fixtures.json",
+        &json!([
+            {
+                "request": { "method": "GET", "path": "/posts" },
+                "response": { "status": 200, "body": [{ "id": 1 }] }
+            },
+            {
+                "request": { "method": "GET", "path": "/logo.png" },
+                "response": { "status": 200, "body": "/wA=", "base64": true }
+            }
+        ]),
+    );
+    let client =
+        Client::tracked(build(&["--replay", fixtures.to_str().unwrap()]).unwrap()).unwrap();
+    assert_eq!(json(client.get("/posts").dispatch()), json!([{ "id": 1 }]));
+    let response = client.get("/logo.png").dispatch();
+    assert_eq!(response.into_bytes(), Some(vec![0xff, 0x00]));
+    assert_eq!(
+        client.get("/comments").dispatch().status(),
+        Status::NotFound
+    );
+}
+
+#[test]
+fn underscore_id() {
+    let dir = TempDir::new("underscore_id");
+    let path = dir.write(
+        "db.json",
+        &json!({ "posts": [{ "_id": 1, "title": "a" }, { "_id": 2, "title": "b" }] }),
+    );
+    let client = start(&path, &["--read-only", "--id", "_id"]);
+    assert_eq!(
+        json(client.get("/posts?_id=1").dispatch()),
+        json!([{ "_id": 1, "title": "a" }])
+    );
+    assert_eq!(
+        json(client.get("/posts?_id_ne=1").dispatch()),
+        json!([{ "_id": 2, "title": "b" }])
+    );
+    assert_eq!(json(client.get("/posts/2").dispatch())["title"], json!("b"));
+}
@@ -0,0 +1,452 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::form::{Form, FromForm};
+use rocket::http::{RawStr, Status};
+use rocket::request::FlashMessage;
+use rocket::response::{content::RawHtml, status, Flash, Redirect};
+use rocket::serde::json::{serde_json, serde_json::json, Value};
+use rocket::{Data, Request, Response, Route, State};
+use rocket_dyn_templates::tera::{Context, Tera};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use super::error::error;
+use super::events::Events;
+use super::id::Ids;
+use super::mount::{Base, Databases};
+use super::schema::Schemas;
+use super::storage::Storage;
+use super::{insert_value, not_found, remove_item, replace_item, Db};
+
+/// 管理页面的前缀, 首页在 `/`
+pub const UI_BASE: &str = "/__ui";
+
+/// 高于资源路由
+const UI_RANK: isize = -20;
+
+/// 最多保留的请求记录数量
+const LOG_SIZE: usize = 50;
+
+/// 模板编译进二进制文件中, 在任意目录下运行都可以使用
+const TEMPLATES: [(&str, &str); 5] = [
+    (
+        "tera/base",
+        include_str!("../../../templates/tera/base.html.tera"),
+    ),
+    (
+        "tera/json_server/layout.html",
+        include_str!("../../../templates/tera/json_server/layout.html.tera"),
+    ),
+    (
+        "tera/json_server/index.html",
+        include_str!("../../../templates/tera/json_server/index.html.tera"),
+    ),
+    (
+        "tera/json_server/resource.html",
+        include_str!("../../../templates/tera/json_server/resource.html.tera"),
+    ),
+    (
+        "tera/json_server/item.html",
+        include_str!("../../../templates/tera/json_server/item.html.tera"),
+    ),
+];
+
+/// 管理页面的模板, 以 .html 结尾的模板会转义输出的内容
+pub struct Pages(Tera);
+
+impl Pages {
+    pub fn new() -> Result<Self, rocket_dyn_templates::tera::Error> {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(TEMPLATES)?;
+        Ok(Pages(tera))
+    }
+
+    fn render(
+        &self,
+        name: &str,
+        base: &str,
+        flash: Option<FlashMessage>,
+        mut context: Value,
+    ) -> Result<RawHtml<String>, status::Custom<Value>> {
+        context["base"] = json!(base);
+        context["ui_base"] = json!(format!("{}{}", base, UI_BASE));
+        if let Some(flash) = flash {
+            context["flash"] = json!({ "kind": flash.kind(), "message": flash.message() });
+        }
+        let context = Context::from_value(context)
+            .map_err(|e| error(Status::InternalServerError, e.to_string()))?;
+        self.0
+            .render(&format!("tera/json_server/{}.html", name), &context)
+            .map(RawHtml)
+            .map_err(|e| error(Status::InternalServerError, format!("{:?}", e)))
+    }
+}
+
+/// 一次请求的记录
+#[derive(Clone)]
+struct LogEntry {
+    /// 请求时间, unix 时间戳, 单位秒
+    time: u64,
+    method: String,
+    path: String,
+    uri: String,
+    status: u16,
+    millis: u128,
+}
+
+/// 请求开始的时间
+struct Started(Instant);
+
+/// 最近的请求记录, 不记录管理页面自身的请求
+#[derive(Clone, Default)]
+pub struct RequestLog(Arc<Mutex<VecDeque<LogEntry>>>);
+
+#[rocket::async_trait]
+impl Fairing for RequestLog {
+    fn info(&self) -> Info {
+        Info {
+            name: "json-server request log",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        req.local_cache(|| Started(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let path = req.uri().path().as_str();
+        let path = req
+            .rocket()
+            .state::<Databases>()
+            .and_then(|databases| databases.strip(path))
+            .unwrap_or(path);
+        if path.is_empty() || path == "/" || path.starts_with(UI_BASE) {
+            return;
+        }
+        let started = req.local_cache(|| Started(Instant::now()));
+        let entry = LogEntry {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            method: req.method().to_string(),
+            path: req.uri().path().to_string(),
+            uri: req.uri().to_string(),
+            status: res.status().code,
+            millis: started.0.elapsed().as_millis(),
+        };
+        let mut log = self.0.lock().unwrap();
+        log.push_front(entry);
+        log.truncate(LOG_SIZE);
+    }
+}
+
+/// 时间戳转换为 UTC 时间 `HH:MM:SS`
+fn format_time(secs: u64) -> String {
+    let secs = secs % 86400;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// 表格中显示的值, 字符串不带引号, 其他值显示为 JSON
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        None => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// 接口的错误信息, 带上详细信息
+fn error_message(err: status::Custom<Value>) -> String {
+    let message = err.1["message"].as_str().unwrap_or_default().to_string();
+    match err.1.get("details") {
+        Some(details) => format!("{} {}", message, details),
+        None => message,
+    }
+}
+
+/// 表单中的 JSON
+#[derive(FromForm)]
+struct JsonForm {
+    json: String,
+}
+
+impl JsonForm {
+    fn parse(&self) -> Result<Value, String> {
+        serde_json::from_str(&self.json).map_err(|e| format!("Invalid JSON: {}", e))
+    }
+}
+
+fn resource_url(base: &Base, name: &str) -> String {
+    format!("{}{}/{}", base, UI_BASE, RawStr::new(name).percent_encode())
+}
+
+fn item_url(base: &Base, name: &str, id: &str) -> String {
+    let id = RawStr::new(id).percent_encode();
+    format!("{}/{}", resource_url(base, name), id)
+}
+
+/// 首页, 所有资源和数量, 以及最近的请求
+/// 有多个数据文件时只显示当前前缀下的请求
+#[rocket::get("/")]
+pub fn dashboard(
+    db: &Db,
+    log: &State<RequestLog>,
+    pages: &State<Pages>,
+    databases: &State<Databases>,
+    base: Base,
+    flash: Option<FlashMessage>,
+) -> Result<RawHtml<String>, status::Custom<Value>> {
+    let resources: Vec<Value> = {
+        let db = db.read();
+        let mut names: Vec<&String> = db.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| match &db[name] {
+                Value::Array(items) => {
+                    json!({ "name": name, "kind": "collection", "count": items.len() })
+                }
+                Value::Object(_) => json!({ "name": name, "kind": "object" }),
+                _ => json!({ "name": name, "kind": "value" }),
+            })
+            .collect()
+    };
+    let requests: Vec<Value> = log
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|entry| {
+            databases
+                .find(&entry.path)
+                .is_some_and(|database| database.mount.base == base.0)
+        })
+        .map(|entry| {
+            json!({
+                "time": format_time(entry.time),
+                "method": entry.method,
+                "uri": entry.uri,
+                "status": entry.status,
+                "millis": entry.millis
+            })
+        })
+        .collect();
+    pages.render(
+        "index",
+        base.0,
+        flash,
+        json!({ "resources": resources, "requests": requests }),
+    )
+}
+
+/// 资源页面, 集合显示为表格, 其他资源显示为 JSON
+#[rocket::get("/<name>")]
+fn resource(
+    name: &str,
+    db: &Db,
+    ids: &State<Ids>,
+    pages: &State<Pages>,
+    base: Base,
+    flash: Option<FlashMessage>,
+) -> Result<RawHtml<String>, status::Custom<Value>> {
+    let db = db.read();
+    let context = match db.get(name) {
+        Some(Value::Array(items)) => {
+            let mut columns: Vec<&String> = items
+                .iter()
+                .filter_map(Value::as_object)
+                .flat_map(|item| item.keys())
+                .filter(|key| **key != ids.field)
+                .collect();
+            columns.sort();
+            columns.dedup();
+            columns.insert(0, &ids.field);
+            let rows: Vec<Value> = items
+                .iter()
+                .map(|item| {
+                    let cells: Vec<String> = columns.iter().map(|c| cell(item.get(*c))).collect();
+                    json!({ "id": cell(Some(ids.get(item))), "cells": cells })
+                })
+                .collect();
+            json!({
+                "name": name,
+                "collection": true,
+                "columns": columns,
+                "rows": rows,
+                "json": "{\n  \n}"
+            })
+        }
+        Some(value) => json!({
+            "name": name,
+            "collection": false,
+            "json": serde_json::to_string_pretty(value).unwrap()
+        }),
+        None => return Err(not_found(format!("Resource {} not found", name))),
+    };
+    pages.render("resource", base.0, flash, context)
+}
+
+/// 集合中添加数据, 其他资源替换数据, 规则见 post_name
+#[rocket::post("/<name>", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+fn save_resource(
+    name: &str,
+    form: Form<JsonForm>,
+    db: &Db,
+    ids: &State<Ids>,
+    schemas: &State<Schemas>,
+    storage: &Storage,
+    events: &Events,
+    base: Base,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(resource_url(&base, name));
+    let data_value = match form.parse() {
+        Ok(data_value) => data_value,
+        Err(message) => return Flash::error(redirect, message),
+    };
+    let mut db = db.write();
+    let is_collection = db.get(name).is_none_or(Value::is_array);
+    match insert_value(&mut db, ids, schemas, storage, events, name, data_value) {
+        Ok(item) if is_collection => Flash::success(
+            redirect,
+            format!("Added item {}", cell(Some(ids.get(&item)))),
+        ),
+        Ok(_) => Flash::success(redirect, format!("Saved {}", name)),
+        Err(err) => Flash::error(redirect, error_message(err)),
+    }
+}
+
+/// 编辑页面
+#[rocket::get("/<name>/<id>")]
+fn item(
+    name: &str,
+    id: &str,
+    db: &Db,
+    ids: &State<Ids>,
+    pages: &State<Pages>,
+    base: Base,
+    flash: Option<FlashMessage>,
+) -> Result<RawHtml<String>, status::Custom<Value>> {
+    let db = db.read();
+    let item = db
+        .get(name)
+        .and_then(Value::as_array)
+        .and_then(|items| items.iter().find(|item| ids.is_value_equal_str(item, id)))
+        .ok_or_else(|| not_found(format!("Item {} not found in {}", id, name)))?;
+    let context = json!({
+        "name": name,
+        "id": id,
+        "json": serde_json::to_string_pretty(item).unwrap()
+    });
+    pages.render("item", base.0, flash, context)
+}
+
+/// 替换数据, 规则见 put_name_id
+#[rocket::post("/<name>/<id>", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+fn save_item(
+    name: &str,
+    id: &str,
+    form: Form<JsonForm>,
+    db: &Db,
+    ids: &State<Ids>,
+    schemas: &State<Schemas>,
+    storage: &Storage,
+    events: &Events,
+    base: Base,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(item_url(&base, name, id));
+    let data_value = match form.parse() {
+        Ok(data_value) => data_value,
+        Err(message) => return Flash::error(redirect, message),
+    };
+    let mut db = db.write();
+    match replace_item(&mut db, ids, schemas, storage, events, name, id, data_value) {
+        Ok(_) => Flash::success(redirect, format!("Saved item {}", id)),
+        Err(err) => Flash::error(redirect, error_message(err)),
+    }
+}
+
+/// 删除数据, 返回资源页面
+#[rocket::post("/<name>/<id>/delete")]
+fn delete_item(
+    name: &str,
+    id: &str,
+    db: &Db,
+    ids: &State<Ids>,
+    storage: &Storage,
+    events: &Events,
+    base: Base,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(resource_url(&base, name));
+    let mut db = db.write();
+    match remove_item(&mut db, ids, storage, events, name, id) {
+        Ok(_) => Flash::success(redirect, format!("Deleted item {}", id)),
+        Err(err) => Flash::error(redirect, error_message(err)),
+    }
+}
+
+/// 默认的 rank 按挂载前的路径计算, 与 `/<name>/<id>` 等资源路由相同, 需要指定更高的优先级
+pub fn routes() -> Vec<Route> {
+    rocket::routes![resource, save_resource, item, save_item, delete_item]
+        .into_iter()
+        .map(|mut route| {
+            route.rank = UI_RANK;
+            route
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_pages() {
+        let pages = Pages::new().unwrap();
+        let html = pages
+            .render(
+                "index",
+                "",
+                None,
+                json!({
+                    "resources": [
+                        { "name": "posts", "kind": "collection", "count": 2 },
+                        { "name": "profile", "kind": "object" }
+                    ],
+                    "requests": [{
+                        "time": format_time(3723),
+                        "method": "GET",
+                        "uri": "/posts?q=<b>",
+                        "status": 200,
+                        "millis": 1
+                    }]
+                }),
+            )
+            .unwrap()
+            .0;
+        assert!(html.contains(r#"<a href="/__ui/posts">posts</a>"#));
+        assert!(html.contains("01:02:03"));
+        assert!(html.contains("posts?q=&lt;b&gt;"));
+
+        let html = pages
+            .render(
+                "resource",
+                "/shop",
+                None,
+                json!({
+                    "name": "posts",
+                    "collection": true,
+                    "columns": ["id", "title"],
+                    "rows": [{ "id": "1", "cells": ["1", "json-server"] }],
+                    "json": "{}"
+                }),
+            )
+            .unwrap()
+            .0;
+        assert!(html.contains(r#"action="/shop/__ui/posts/1/delete""#));
+        assert!(html.contains("<td><code>json-server</code></td>"));
+    }
+}
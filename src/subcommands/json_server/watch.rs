@@ -0,0 +1,94 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::tokio::time;
+use rocket::{Orbit, Rocket};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use std::{fs, io};
+
+use super::events::Events;
+use super::storage::{Format, Storage};
+use super::Db;
+
+/// 检查文件变化的间隔
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 监听数据文件的变化, 文件被外部修改后重新加载到内存中
+/// 文件格式错误时只打印错误, 保留原来的数据
+pub struct Watcher {
+    path: PathBuf,
+    db: Db,
+    storage: Storage,
+    events: Events,
+}
+
+impl Watcher {
+    pub fn new(path: PathBuf, db: Db, storage: Storage, events: Events) -> Self {
+        Watcher {
+            path,
+            db,
+            storage,
+            events,
+        }
+    }
+
+    /// 文件的修改时间和大小, 任意一个变化都认为文件被修改
+    fn stamp(&self) -> io::Result<(SystemTime, u64)> {
+        let metadata = fs::metadata(&self.path)?;
+        Ok((metadata.modified()?, metadata.len()))
+    }
+
+    /// 重新加载文件, 文件内容是自己写入的则忽略
+    /// 读取的内容直接解析, 不再重新读取文件
+    fn reload(&self) {
+        let result = self.storage.reload_external(&self.path, |content| {
+            match Format::from_path(&self.path).parse(content) {
+                Ok(data) => {
+                    let mut db = self.db.write();
+                    self.storage.discard();
+                    self.events.replaced(&db, &data);
+                    *db = data;
+                    println!("Reloaded {}", self.path.display());
+                }
+                Err(e) => println!("Unable to reload {}: {}", self.path.display(), e),
+            }
+        });
+        if let Err(e) = result {
+            println!("Unable to read file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Watcher {
+    fn info(&self) -> Info {
+        Info {
+            name: "json-server watcher",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let watcher = Watcher::new(
+            self.path.clone(),
+            self.db.clone(),
+            self.storage.clone(),
+            self.events.clone(),
+        );
+        let mut shutdown = rocket.shutdown();
+        rocket::tokio::spawn(async move {
+            let mut last = watcher.stamp().ok();
+            loop {
+                rocket::tokio::select! {
+                    _ = &mut shutdown => break,
+                    _ = time::sleep(WATCH_INTERVAL) => {}
+                }
+                let stamp = watcher.stamp().ok();
+                if stamp.is_none() || stamp == last {
+                    continue;
+                }
+                last = stamp;
+                watcher.reload();
+            }
+        });
+    }
+}
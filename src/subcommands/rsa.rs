@@ -63,7 +63,7 @@ fn mod_inverse(a: &BigUint, n: &BigUint) -> BigUint {
     );
 
     // 使用扩展欧几里得算法计算模反元素
-    while &a > &BigInt::one() {
+    while a > BigInt::one() {
         let div = &a / &m;
         let rem = &a % &m;
         let temp = &inv - &div * &x;
@@ -74,7 +74,7 @@ fn mod_inverse(a: &BigUint, n: &BigUint) -> BigUint {
     }
 
     // 处理负数情况
-    if &inv < &BigInt::zero() {
+    if inv < BigInt::zero() {
         inv += n.to_bigint().unwrap();
     }
 